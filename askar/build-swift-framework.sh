@@ -29,7 +29,7 @@ targets=("aarch64-apple-ios" "aarch64-apple-ios-sim" "x86_64-apple-ios" "aarch64
 for target in "${targets[@]}"; do
   echo "Building for $target..."
   rustup target add $target
-  cargo build --release --target $target
+  cargo build --release --target $target ${ASKAR_FEATURES:+--features $ASKAR_FEATURES}
 done
 
 # Generate swift wrapper
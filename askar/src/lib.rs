@@ -1,11 +1,17 @@
 mod uffi;
 use uffi::{
     crypto::{AskarCrypto, AskarEcdh1PU, AskarEcdhEs},
+    didcomm::AskarDidCommV1,
     entry::{AskarEntry, AskarKeyEntry},
+    filter::AskarTagFilter,
+    jwe::AskarJwe,
+    jws::AskarJws,
     key::{AskarLocalKey, EncryptedBuffer, LocalKeyFactory},
     scan::AskarScan,
     session::AskarSession,
     store::{AskarStore, AskarStoreManager},
+    stream::AskarAeadStream,
+    subscription::AskarSubscription,
 };
 
 uniffi::include_scaffolding!("askar");
@@ -0,0 +1,273 @@
+use crate::uffi::{
+    encoding::{b64_url_decode, b64_url_encode_no_pad},
+    error::ErrorCode,
+    expiry::remaining_expiry_ms,
+    key::{passphrase_decrypt, passphrase_encrypt, AskarKdfParams},
+    reference::AskarKeyReference,
+    store::AskarStoreCopyStats,
+    store_key::{resolve_key_method, AskarPassKey},
+    tags::EntryTagSet,
+};
+use aries_askar::{
+    crypto::alg::{AesTypes, KeyAlg},
+    kms::LocalKey,
+    storage::{entry::EntryOperation, Options},
+    ErrorKind, Store, StoreKeyMethod,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::{fs::OpenOptions, io};
+
+const BACKUP_VERSION: u64 = 1;
+const BACKUP_CTY: &str = "askar-backup+json";
+const BACKUP_ENC: KeyAlg = KeyAlg::Aes(AesTypes::A256Gcm);
+
+// The backup document is independent of the storage backend: entries and keys
+// are written in plaintext form and re-encrypted by the store they are restored into.
+// Expiry times are carried in the tags recorded by this wrapper.
+#[derive(Serialize, Deserialize)]
+struct Backup {
+    version: u64,
+    default_profile: String,
+    profiles: Vec<BackupProfile>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BackupProfile {
+    name: String,
+    entries: Vec<BackupEntry>,
+    keys: Vec<BackupKey>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BackupEntry {
+    category: String,
+    name: String,
+    value: String,
+    tags: EntryTagSet<'static>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BackupKey {
+    name: String,
+    metadata: Option<String>,
+    tags: EntryTagSet<'static>,
+    jwk: Value,
+}
+
+async fn backup_profile(store: &Store, profile: String) -> Result<BackupProfile, ErrorCode> {
+    let mut backup = BackupProfile {
+        name: profile.clone(),
+        entries: vec![],
+        keys: vec![],
+    };
+    let mut scan = store
+        .scan(Some(profile.clone()), None, None, None, None)
+        .await?;
+    while let Some(entries) = scan.fetch_next().await? {
+        for entry in entries {
+            backup.entries.push(BackupEntry {
+                category: entry.category,
+                name: entry.name,
+                value: b64_url_encode_no_pad(&entry.value),
+                tags: EntryTagSet::from(entry.tags),
+            });
+        }
+    }
+    let keys = store
+        .session(Some(profile))
+        .await?
+        .fetch_all_keys(None, None, None, None, false)
+        .await?;
+    for key in keys {
+        // Only the public part of an externally held key is stored
+        let local_key = key.load_local_key()?;
+        let jwk = match AskarKeyReference::from_tags(key.tags_as_slice()) {
+            AskarKeyReference::Local => local_key.to_jwk_secret()?.into_vec(),
+            AskarKeyReference::External { .. } => local_key.to_jwk_public(None)?.into_bytes(),
+        };
+        backup.keys.push(BackupKey {
+            name: key.name().to_string(),
+            metadata: key.metadata().map(String::from),
+            tags: EntryTagSet::from(key.tags_as_slice().to_vec()),
+            jwk: serde_json::from_slice(&jwk).map_err(|err| ErrorCode::Unexpected {
+                message: format!("Error encoding key for backup: {}", err),
+            })?,
+        });
+    }
+    Ok(backup)
+}
+
+async fn restore_profile(
+    store: &Store,
+    profile: BackupProfile,
+    stats: &mut AskarStoreCopyStats,
+) -> Result<(), ErrorCode> {
+    let mut target = store.transaction(Some(profile.name)).await?;
+    for entry in profile.entries {
+        let value = b64_url_decode(&entry.value)?;
+        let tags = entry.tags.into_vec();
+        target
+            .update(
+                EntryOperation::Insert,
+                &entry.category,
+                &entry.name,
+                Some(&value),
+                Some(&tags),
+                remaining_expiry_ms(&tags),
+            )
+            .await?;
+        stats.entries += 1;
+    }
+    for key in profile.keys {
+        let local_key = LocalKey::from_jwk(&key.jwk.to_string())?;
+        let tags = key.tags.into_vec();
+        let (reference, _) = AskarKeyReference::from_tags(&tags).into_parts();
+        target
+            .insert_key(
+                &key.name,
+                &local_key,
+                key.metadata.as_deref(),
+                reference,
+                Some(&tags),
+                remaining_expiry_ms(&tags),
+            )
+            .await?;
+        stats.keys += 1;
+    }
+    target.commit().await?;
+    stats.profiles += 1;
+    Ok(())
+}
+
+async fn restore_profiles(store: &Store, backup: Backup) -> Result<AskarStoreCopyStats, ErrorCode> {
+    let mut stats = AskarStoreCopyStats {
+        profiles: 0,
+        entries: 0,
+        keys: 0,
+    };
+    for profile in backup.profiles {
+        if profile.name != backup.default_profile {
+            store.create_profile(Some(profile.name.clone())).await?;
+        }
+        restore_profile(store, profile, &mut stats).await?;
+    }
+    Ok(stats)
+}
+
+pub(crate) async fn write_backup(
+    store: &Store,
+    path: &str,
+    pass_key: &str,
+) -> Result<AskarStoreCopyStats, ErrorCode> {
+    let mut backup = Backup {
+        version: BACKUP_VERSION,
+        default_profile: store.get_default_profile().await?,
+        profiles: vec![],
+    };
+    for profile in store.list_profiles().await? {
+        backup.profiles.push(backup_profile(store, profile).await?);
+    }
+    let stats = AskarStoreCopyStats {
+        profiles: backup.profiles.len() as i64,
+        entries: backup.profiles.iter().map(|p| p.entries.len() as i64).sum(),
+        keys: backup.profiles.iter().map(|p| p.keys.len() as i64).sum(),
+    };
+
+    let document = serde_json::to_vec(&backup).map_err(|err| ErrorCode::Unexpected {
+        message: format!("Error encoding backup: {}", err),
+    })?;
+    let blob = passphrase_encrypt(
+        &document,
+        BACKUP_CTY,
+        pass_key,
+        AskarKdfParams::Argon2iModerate,
+        BACKUP_ENC,
+    )?;
+    std::fs::write(path, blob).map_err(|err| ErrorCode::Backend {
+        message: format!("Error writing backup file: {}", err),
+    })?;
+    Ok(stats)
+}
+
+pub(crate) async fn restore_backup(
+    path: &str,
+    pass_key: &str,
+    target_uri: &str,
+    target_pass_key: AskarPassKey,
+) -> Result<AskarStoreCopyStats, ErrorCode> {
+    let blob = std::fs::read_to_string(path).map_err(|err| ErrorCode::Backend {
+        message: format!("Error reading backup file: {}", err),
+    })?;
+    let document = passphrase_decrypt(&blob, BACKUP_CTY, pass_key)?;
+    let backup: Backup = serde_json::from_slice(&document)
+        .map_err(|err| ErrorCode::input(format!("Invalid backup document: {}", err)))?;
+    if backup.version != BACKUP_VERSION {
+        return Err(ErrorCode::Unsupported {
+            message: format!("Unsupported backup version: {}", backup.version),
+        });
+    }
+
+    let key_method = resolve_key_method(None, &target_pass_key)?;
+    let created = claim_target(target_uri, &target_pass_key).await?;
+    let restored = restore_into(target_uri, key_method, &target_pass_key, backup).await;
+    if restored.is_err() && created {
+        Store::remove(target_uri).await.ok();
+    }
+    restored
+}
+
+async fn restore_into(
+    target_uri: &str,
+    key_method: StoreKeyMethod,
+    target_pass_key: &AskarPassKey,
+    backup: Backup,
+) -> Result<AskarStoreCopyStats, ErrorCode> {
+    let store = Store::provision(
+        target_uri,
+        key_method,
+        target_pass_key.to_pass_key(),
+        Some(backup.default_profile.clone()),
+        false,
+    )
+    .await?;
+    let restored = restore_profiles(&store, backup).await;
+    let closed = store.close().await.map_err(ErrorCode::from);
+    restored.and_then(|stats| closed.map(|_| stats))
+}
+
+// Provisioning opens an existing store, so the target must not exist. A SQLite database
+// file is claimed by creating it exclusively, and may be removed if the restore fails.
+// Other stores are refused if they can be opened or fail for any reason other than not
+// being found; they are never removed, as another client may create them concurrently.
+async fn claim_target(target_uri: &str, pass_key: &AskarPassKey) -> Result<bool, ErrorCode> {
+    let options = Options::parse_uri(target_uri)?;
+    if options.scheme == "sqlite" {
+        let path = format!("{}{}", options.host, options.path);
+        if path == ":memory:" {
+            return Ok(false);
+        }
+        return match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(_) => Ok(true),
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => Err(target_exists_error()),
+            Err(err) => Err(ErrorCode::Backend {
+                message: format!("Error creating restore target: {}", err),
+            }),
+        };
+    }
+    match Store::open(target_uri, None, pass_key.to_pass_key(), None).await {
+        Ok(existing) => {
+            existing.close().await.ok();
+            Err(target_exists_error())
+        }
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(false),
+        Err(err) if err.kind() == ErrorKind::Backend => Err(err.into()),
+        Err(_) => Err(target_exists_error()),
+    }
+}
+
+fn target_exists_error() -> ErrorCode {
+    ErrorCode::Duplicate {
+        message: String::from("Restore target store already exists"),
+    }
+}
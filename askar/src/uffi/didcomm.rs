@@ -0,0 +1,333 @@
+use crate::uffi::{
+    encoding::{b58_decode, b58_encode, b64_url_decode, b64_url_encode},
+    error::ErrorCode,
+    key::AskarLocalKey,
+};
+use aries_askar::{
+    crypto::alg::{Chacha20Types, KeyAlg},
+    kms::{
+        crypto_box, crypto_box_open, crypto_box_random_nonce, crypto_box_seal,
+        crypto_box_seal_open, LocalKey,
+    },
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+const ENC_XC20P: &str = "xchacha20poly1305_ietf";
+const TYP_JWM: &str = "JWM/1.0";
+const ALG_AUTHCRYPT: &str = "Authcrypt";
+const ALG_ANONCRYPT: &str = "Anoncrypt";
+
+#[derive(Serialize, Deserialize)]
+struct Envelope {
+    protected: String,
+    iv: String,
+    ciphertext: String,
+    tag: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ProtectedHeader {
+    enc: String,
+    typ: String,
+    alg: String,
+    recipients: Vec<Recipient>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Recipient {
+    encrypted_key: String,
+    header: RecipientHeader,
+}
+
+#[derive(Serialize, Deserialize)]
+struct RecipientHeader {
+    kid: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sender: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    iv: Option<String>,
+}
+
+#[derive(uniffi::Record)]
+pub struct AskarUnpackedMessage {
+    pub message: Vec<u8>,
+    pub recipient_verkey: String,
+    pub sender_verkey: Option<String>,
+}
+
+fn ed25519_verkey(key: &LocalKey) -> Result<String, ErrorCode> {
+    if key.algorithm() != KeyAlg::Ed25519 {
+        return Err(ErrorCode::Unsupported {
+            message: String::from("DIDComm v1 requires Ed25519 keys"),
+        });
+    }
+    Ok(b58_encode(&key.to_public_bytes()?))
+}
+
+fn x25519_from_verkey(verkey: &str) -> Result<LocalKey, ErrorCode> {
+    let public = LocalKey::from_public_bytes(KeyAlg::Ed25519, &b58_decode(verkey)?)?;
+    Ok(public.convert_key(KeyAlg::X25519)?)
+}
+
+/// Looks up the private keys of envelope recipients, such as from a store session.
+#[uniffi::export(callback_interface)]
+pub trait AskarDidCommKeyResolver: Send + Sync {
+    /// Return the Ed25519 key for the given base58 verkey, or `None` if it is not held.
+    fn resolve_key(&self, verkey: String) -> Result<Option<Arc<AskarLocalKey>>, ErrorCode>;
+}
+
+/// Aries RFC 0019 encryption envelopes, using XChaCha20-Poly1305 for the
+/// payload and libsodium-compatible boxes for the per-recipient content key.
+pub struct AskarDidCommV1 {}
+
+impl AskarDidCommV1 {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+#[uniffi::export]
+impl AskarDidCommV1 {
+    pub fn pack(
+        &self,
+        message: Vec<u8>,
+        recipient_verkeys: Vec<String>,
+        sender_key: Option<Arc<AskarLocalKey>>,
+    ) -> Result<String, ErrorCode> {
+        if recipient_verkeys.is_empty() {
+            return Err(ErrorCode::input("No message recipients"));
+        }
+        let cek = LocalKey::generate_with_rng(KeyAlg::Chacha20(Chacha20Types::XC20P), true)?;
+        let cek_bytes = cek.to_secret_bytes()?;
+        let sender = match &sender_key {
+            Some(sender_key) => Some((
+                ed25519_verkey(&sender_key.key)?,
+                sender_key.key.convert_key(KeyAlg::X25519)?,
+            )),
+            None => None,
+        };
+
+        let mut recipients = Vec::with_capacity(recipient_verkeys.len());
+        for verkey in recipient_verkeys {
+            let recip_x = x25519_from_verkey(&verkey)?;
+            let recipient = match &sender {
+                Some((sender_verkey, sender_x)) => {
+                    let nonce = crypto_box_random_nonce()?;
+                    let encrypted_key = crypto_box(&recip_x, sender_x, &cek_bytes, &nonce)?;
+                    let enc_sender = crypto_box_seal(&recip_x, sender_verkey.as_bytes())?;
+                    Recipient {
+                        encrypted_key: b64_url_encode(&encrypted_key),
+                        header: RecipientHeader {
+                            kid: verkey,
+                            sender: Some(b64_url_encode(&enc_sender)),
+                            iv: Some(b64_url_encode(&nonce)),
+                        },
+                    }
+                }
+                None => Recipient {
+                    encrypted_key: b64_url_encode(&crypto_box_seal(&recip_x, &cek_bytes)?),
+                    header: RecipientHeader {
+                        kid: verkey,
+                        sender: None,
+                        iv: None,
+                    },
+                },
+            };
+            recipients.push(recipient);
+        }
+
+        let protected = ProtectedHeader {
+            enc: ENC_XC20P.to_string(),
+            typ: TYP_JWM.to_string(),
+            alg: if sender.is_some() {
+                ALG_AUTHCRYPT
+            } else {
+                ALG_ANONCRYPT
+            }
+            .to_string(),
+            recipients,
+        };
+        let protected = serde_json::to_vec(&protected).map_err(|err| ErrorCode::Unexpected {
+            message: format!("Error encoding protected header: {}", err),
+        })?;
+        let protected = b64_url_encode(&protected);
+
+        let nonce = cek.aead_random_nonce()?;
+        let enc = cek.aead_encrypt(&message, &nonce, protected.as_bytes())?;
+        let envelope = Envelope {
+            protected,
+            iv: b64_url_encode(enc.nonce()),
+            ciphertext: b64_url_encode(enc.ciphertext()),
+            tag: b64_url_encode(enc.tag()),
+        };
+        serde_json::to_string(&envelope).map_err(|err| ErrorCode::Unexpected {
+            message: format!("Error encoding envelope: {}", err),
+        })
+    }
+
+    pub fn recipient_verkeys(&self, envelope: String) -> Result<Vec<String>, ErrorCode> {
+        let (_, protected) = parse_envelope(&envelope)?;
+        Ok(protected
+            .recipients
+            .into_iter()
+            .map(|recipient| recipient.header.kid)
+            .collect())
+    }
+
+    /// Unpack an envelope, using the first recipient whose key is resolved.
+    pub fn unpack(
+        &self,
+        envelope: String,
+        key_resolver: Box<dyn AskarDidCommKeyResolver>,
+    ) -> Result<AskarUnpackedMessage, ErrorCode> {
+        let (envelope, protected) = parse_envelope(&envelope)?;
+        let mut found = None;
+        for recipient in &protected.recipients {
+            if let Some(key) = key_resolver.resolve_key(recipient.header.kid.clone())? {
+                if ed25519_verkey(&key.key)? != recipient.header.kid {
+                    return Err(ErrorCode::input(format!(
+                        "Resolved key does not match recipient: {}",
+                        recipient.header.kid
+                    )));
+                }
+                found = Some((key, recipient));
+                break;
+            }
+        }
+        let (recip_key, recipient) = found.ok_or(ErrorCode::NotFound {
+            message: String::from("No corresponding recipient key found"),
+        })?;
+        let recip_x = recip_key.key.convert_key(KeyAlg::X25519)?;
+        let encrypted_key = b64_url_decode(&recipient.encrypted_key)?;
+
+        let (cek_bytes, sender_verkey) = match protected.alg.as_str() {
+            ALG_AUTHCRYPT => {
+                let (Some(sender), Some(iv)) = (&recipient.header.sender, &recipient.header.iv)
+                else {
+                    return Err(ErrorCode::input("Missing sender or iv in recipient header"));
+                };
+                let sender_verkey = crypto_box_seal_open(&recip_x, &b64_url_decode(sender)?)?;
+                let sender_verkey = String::from_utf8(sender_verkey.to_vec())
+                    .map_err(|_| ErrorCode::input("Invalid sender verkey"))?;
+                let sender_x = x25519_from_verkey(&sender_verkey)?;
+                let cek =
+                    crypto_box_open(&recip_x, &sender_x, &encrypted_key, &b64_url_decode(iv)?)?;
+                (cek, Some(sender_verkey))
+            }
+            ALG_ANONCRYPT => (crypto_box_seal_open(&recip_x, &encrypted_key)?, None),
+            alg => {
+                return Err(ErrorCode::Unsupported {
+                    message: format!("Unsupported pack algorithm: {}", alg),
+                })
+            }
+        };
+
+        let cek = LocalKey::from_secret_bytes(KeyAlg::Chacha20(Chacha20Types::XC20P), &cek_bytes)?;
+        let ciphertext = b64_url_decode(&envelope.ciphertext)?;
+        let tag = b64_url_decode(&envelope.tag)?;
+        let nonce = b64_url_decode(&envelope.iv)?;
+        let message = cek.aead_decrypt(
+            (ciphertext.as_slice(), tag.as_slice()),
+            &nonce,
+            envelope.protected.as_bytes(),
+        )?;
+        Ok(AskarUnpackedMessage {
+            message: message.to_vec(),
+            recipient_verkey: recipient.header.kid.clone(),
+            sender_verkey,
+        })
+    }
+}
+
+fn parse_envelope(envelope: &str) -> Result<(Envelope, ProtectedHeader), ErrorCode> {
+    let envelope: Envelope = serde_json::from_str(envelope)
+        .map_err(|err| ErrorCode::input(format!("Invalid envelope: {}", err)))?;
+    let protected: ProtectedHeader = serde_json::from_slice(&b64_url_decode(&envelope.protected)?)
+        .map_err(|err| ErrorCode::input(format!("Invalid protected header: {}", err)))?;
+    if protected.enc != ENC_XC20P {
+        return Err(ErrorCode::Unsupported {
+            message: format!("Unsupported encryption algorithm: {}", protected.enc),
+        });
+    }
+    Ok((envelope, protected))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Envelopes packed by an independent RFC 0019 implementation, using Ed25519 seeds
+    // 0x00..0x1f for the recipient and 0x20..0x3f for the sender
+    const RECIPIENT_VERKEY: &str = "FAe4sisG95oZ42w7buUn5qEE4TAnfTTFPiguZUHmhiF";
+    const SENDER_VERKEY: &str = "3ogUn1GNXoASaRbxPNeVJnVv5rG4EPBtmQmX61jVorUe";
+    const MESSAGE: &str = r#"{"@type":"https://didcomm.org/trust_ping/1.0/ping"}"#;
+    const ANONCRYPT_ENVELOPE: &str = concat!(
+        r#"{"protected":"eyJlbmMiOiJ4Y2hhY2hhMjBwb2x5MTMwNV9pZXRmIiwidHlwIjoiSldNLzEuMCIsIm"#,
+        r#"FsZyI6IkFub25jcnlwdCIsInJlY2lwaWVudHMiOlt7ImVuY3J5cHRlZF9rZXkiOiJTVDZDX0hSR1Nsa2"#,
+        r#"1pQmRpUFNCVHhldU9MTVNwaUxULTRYbnNhd0VOVXgzamk2QWlsM0ZFeWxxbjIxLVVCaU5ESWZzRE01N1"#,
+        r#"ZXVlBSUDFGS2pJYldlaUlaVmFlcFB2cjdJR3BQeUhQSGEyWSIsImhlYWRlciI6eyJraWQiOiJGQWU0c2"#,
+        r#"lzRzk1b1o0Mnc3YnVVbjVxRUU0VEFuZlRURlBpZ3VaVUhtaGlGIn19XX0","iv":"YGFiY2RlZmdoaWp"#,
+        r#"rbG1ub3BxcnN0dXZ3","ciphertext":"7lyzEQpihgnHkV8OLrKu0dMIzd-ZAokqjEGUuPB_K3DLdXR"#,
+        r#"xItsTWl3C7OuKIzMhKYq0","tag":"qbGfxU4k9Bi3YX2Zo_iQJg"}"#,
+    );
+    const AUTHCRYPT_ENVELOPE: &str = concat!(
+        r#"{"protected":"eyJlbmMiOiJ4Y2hhY2hhMjBwb2x5MTMwNV9pZXRmIiwidHlwIjoiSldNLzEuMCIsIm"#,
+        r#"FsZyI6IkF1dGhjcnlwdCIsInJlY2lwaWVudHMiOlt7ImVuY3J5cHRlZF9rZXkiOiJTV201c3MxV19OZj"#,
+        r#"dySDJPaTVzTGFpTEs2TjZwdHJiWmRHdmxpb1RIcGp2SXlEbk91VVBTRmlaWnlVNzBUdTZuIiwiaGVhZG"#,
+        r#"VyIjp7ImtpZCI6IkZBZTRzaXNHOTVvWjQydzdidVVuNXFFRTRUQW5mVFRGUGlndVpVSG1oaUYiLCJzZW"#,
+        r#"5kZXIiOiJTVDZDX0hSR1Nsa21pQmRpUFNCVHhldU9MTVNwaUxULTRYbnNhd0VOVXgyaHl1UnY4RUd4TD"#,
+        r#"ZZakRjZTRzYUdqVXRVbUpiU2hXRnJCR1ZwU29abjZUU0lHWXFLM0JmcmFUVUZTcDJyS2QwM3BWM1pFWl"#,
+        r#"M3N2tlTzJDbjQiLCJpdiI6Im9LR2lvNlNscHFlb3FhcXJySzJ1cjdDeHNyTzB0YmEzIn19XX0","iv":"#,
+        r#""YGFiY2RlZmdoaWprbG1ub3BxcnN0dXZ3","ciphertext":"7lyzEQpihgnHkV8OLrKu0dMIzd-ZAok"#,
+        r#"qjEGUuPB_K3DLdXRxItsTWl3C7OuKIzMhKYq0","tag":"uvhh6ZQ-6fQVDj9PqvYM8Q"}"#,
+    );
+
+    struct SeedResolver(Vec<Arc<AskarLocalKey>>);
+
+    impl AskarDidCommKeyResolver for SeedResolver {
+        fn resolve_key(&self, verkey: String) -> Result<Option<Arc<AskarLocalKey>>, ErrorCode> {
+            for key in &self.0 {
+                if ed25519_verkey(&key.key)? == verkey {
+                    return Ok(Some(key.clone()));
+                }
+            }
+            Ok(None)
+        }
+    }
+
+    fn seed_key(start: u8) -> Arc<AskarLocalKey> {
+        let seed: Vec<u8> = (start..start + 32).collect();
+        let key = LocalKey::from_secret_bytes(KeyAlg::Ed25519, &seed).unwrap();
+        Arc::new(AskarLocalKey { key })
+    }
+
+    fn unpack(
+        envelope: &str,
+        keys: Vec<Arc<AskarLocalKey>>,
+    ) -> Result<AskarUnpackedMessage, ErrorCode> {
+        AskarDidCommV1::new().unpack(envelope.to_string(), Box::new(SeedResolver(keys)))
+    }
+
+    #[test]
+    fn unpack_anoncrypt_vector() {
+        let unpacked = unpack(ANONCRYPT_ENVELOPE, vec![seed_key(0)]).unwrap();
+        assert_eq!(unpacked.message, MESSAGE.as_bytes());
+        assert_eq!(unpacked.recipient_verkey, RECIPIENT_VERKEY);
+        assert_eq!(unpacked.sender_verkey, None);
+    }
+
+    #[test]
+    fn unpack_authcrypt_vector() {
+        let unpacked = unpack(AUTHCRYPT_ENVELOPE, vec![seed_key(0)]).unwrap();
+        assert_eq!(unpacked.message, MESSAGE.as_bytes());
+        assert_eq!(unpacked.recipient_verkey, RECIPIENT_VERKEY);
+        assert_eq!(unpacked.sender_verkey.as_deref(), Some(SENDER_VERKEY));
+    }
+
+    #[test]
+    fn unpack_unresolved_recipient() {
+        let result = unpack(AUTHCRYPT_ENVELOPE, vec![seed_key(32)]);
+        assert!(matches!(result, Err(ErrorCode::NotFound { .. })));
+    }
+}
@@ -0,0 +1,40 @@
+use crate::uffi::error::ErrorCode;
+use base64::{
+    alphabet,
+    engine::{general_purpose, DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig},
+    Engine,
+};
+
+// Accepts both padded and unpadded input, as implementations disagree
+const URL_SAFE_INDIFFERENT: GeneralPurpose = GeneralPurpose::new(
+    &alphabet::URL_SAFE,
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent),
+);
+
+pub(crate) fn b64_url_encode(data: &[u8]) -> String {
+    general_purpose::URL_SAFE.encode(data)
+}
+
+pub(crate) fn b64_url_encode_no_pad(data: &[u8]) -> String {
+    general_purpose::URL_SAFE_NO_PAD.encode(data)
+}
+
+pub(crate) fn b64_url_decode(data: &str) -> Result<Vec<u8>, ErrorCode> {
+    URL_SAFE_INDIFFERENT
+        .decode(data)
+        .map_err(|err| ErrorCode::Input {
+            message: format!("Invalid base64url value: {}", err),
+        })
+}
+
+pub(crate) fn b58_encode(data: &[u8]) -> String {
+    bs58::encode(data).into_string()
+}
+
+pub(crate) fn b58_decode(data: &str) -> Result<Vec<u8>, ErrorCode> {
+    bs58::decode(data)
+        .into_vec()
+        .map_err(|err| ErrorCode::Input {
+            message: format!("Invalid base58 value: {}", err),
+        })
+}
@@ -1,8 +1,92 @@
-use crate::uffi::{error::ErrorCode, key::AskarLocalKey};
-use aries_askar::{kms::KeyEntry, storage::entry::Entry};
+use crate::uffi::{
+    error::ErrorCode,
+    expiry::expiry_from_tags,
+    key::{AskarKeyAlg, AskarLocalKey},
+    reference::{external_handler, AskarExternalKeyHandler, AskarKeyReference},
+    reserved::{check_tag_names, is_reserved_tag},
+    rotation::version_from_tags,
+};
+use aries_askar::{
+    crypto::alg::KeyAlg,
+    kms::{KeyEntry, LocalKey},
+    storage::entry::{Entry, EntryTag},
+};
+use serde_json::Value;
 use std::collections::HashMap;
 use std::sync::Arc;
 
+#[derive(uniffi::Record)]
+pub struct AskarEntryTag {
+    pub name: String,
+    pub value: String,
+    pub plaintext: bool,
+}
+
+impl From<AskarEntryTag> for EntryTag {
+    fn from(tag: AskarEntryTag) -> Self {
+        if tag.plaintext {
+            EntryTag::Plaintext(tag.name, tag.value)
+        } else {
+            EntryTag::Encrypted(tag.name, tag.value)
+        }
+    }
+}
+
+impl From<&EntryTag> for AskarEntryTag {
+    fn from(tag: &EntryTag) -> Self {
+        match tag {
+            EntryTag::Encrypted(name, value) => AskarEntryTag {
+                name: name.clone(),
+                value: value.clone(),
+                plaintext: false,
+            },
+            EntryTag::Plaintext(name, value) => AskarEntryTag {
+                name: name.clone(),
+                value: value.clone(),
+                plaintext: true,
+            },
+        }
+    }
+}
+
+/// Convert the tags given by a caller, which must not use the reserved tag names.
+pub(crate) fn into_entry_tags(
+    tags: Option<Vec<AskarEntryTag>>,
+) -> Result<Option<Vec<EntryTag>>, ErrorCode> {
+    let tags = tags.map(|tags| tags.into_iter().map(EntryTag::from).collect::<Vec<_>>());
+    if let Some(tags) = &tags {
+        check_tag_names(tags)?;
+    }
+    Ok(tags)
+}
+
+pub(crate) fn parse_json(value: &[u8]) -> Result<Value, ErrorCode> {
+    serde_json::from_slice(value).map_err(|err| ErrorCode::Input {
+        message: format!("Invalid JSON value: {}", err),
+    })
+}
+
+/// Apply a JSON merge patch (RFC 7396) to a value.
+pub(crate) fn merge_json_patch(target: &mut Value, patch: Value) {
+    match patch {
+        Value::Object(patch) => {
+            if !target.is_object() {
+                *target = Value::Object(Default::default());
+            }
+            if let Value::Object(target) = target {
+                for (name, value) in patch {
+                    if value.is_null() {
+                        target.remove(&name);
+                    } else {
+                        merge_json_patch(target.entry(name).or_insert(Value::Null), value);
+                    }
+                }
+            }
+        }
+        patch => *target = patch,
+    }
+}
+
 pub struct AskarEntry {
     entry: Entry,
 }
@@ -26,14 +110,45 @@ impl AskarEntry {
     pub fn tags(&self) -> HashMap<String, String> {
         let mut map = HashMap::new();
         for tag in &self.entry.tags {
-            map.insert(tag.name().to_string(), tag.value().to_string());
+            if !is_reserved_tag(tag) {
+                map.insert(tag.name().to_string(), tag.value().to_string());
+            }
         }
         map
     }
 
+    pub fn tags_list(&self) -> Vec<AskarEntryTag> {
+        self.entry
+            .tags
+            .iter()
+            .filter(|tag| !is_reserved_tag(tag))
+            .map(AskarEntryTag::from)
+            .collect()
+    }
+
+    /// The expiry time in milliseconds since the Unix epoch, for entries
+    /// written through this wrapper with an expiry. Expiry times are read from a tag
+    /// recorded by this wrapper, as aries-askar does not expose them: entries written
+    /// before this tag was recorded, or by other Askar clients, report no expiry.
+    /// Entries copied with `copy_to` keep their expiry time, but are no longer
+    /// expired by aries-askar.
+    pub fn expiry(&self) -> Option<i64> {
+        expiry_from_tags(&self.entry.tags)
+    }
+
     pub fn value(&self) -> Vec<u8> {
         self.entry.value.to_vec()
     }
+
+    pub fn value_as_string(&self) -> Result<String, ErrorCode> {
+        String::from_utf8(self.entry.value.to_vec()).map_err(|err| ErrorCode::Input {
+            message: format!("Entry value is not valid UTF-8: {}", err),
+        })
+    }
+
+    pub fn value_as_json(&self) -> Result<String, ErrorCode> {
+        Ok(parse_json(&self.entry.value)?.to_string())
+    }
 }
 
 pub struct AskarKeyEntry {
@@ -64,16 +179,102 @@ impl AskarKeyEntry {
         self.entry.is_local()
     }
 
+    /// The expiry time in milliseconds since the Unix epoch, for keys
+    /// written through this wrapper with an expiry. As for `AskarEntry::expiry`,
+    /// keys written by other Askar clients report no expiry.
+    pub fn expiry(&self) -> Option<i64> {
+        expiry_from_tags(self.entry.tags_as_slice())
+    }
+
+    pub fn reference(&self) -> AskarKeyReference {
+        AskarKeyReference::from_tags(self.entry.tags_as_slice())
+    }
+
+    /// The version of the key, which is incremented each time the key is rotated.
+    pub fn version(&self) -> i64 {
+        version_from_tags(self.entry.tags_as_slice())
+    }
+
     pub fn tags(&self) -> HashMap<String, String> {
         let mut map = HashMap::new();
         for tag in self.entry.tags_as_slice() {
-            map.insert(tag.name().to_string(), tag.value().to_string());
+            if !is_reserved_tag(tag) {
+                map.insert(tag.name().to_string(), tag.value().to_string());
+            }
         }
         map
     }
 
+    pub fn tags_list(&self) -> Vec<AskarEntryTag> {
+        self.entry
+            .tags_as_slice()
+            .iter()
+            .filter(|tag| !is_reserved_tag(tag))
+            .map(AskarEntryTag::from)
+            .collect()
+    }
+
     pub fn load_local_key(&self) -> Result<Arc<AskarLocalKey>, ErrorCode> {
         let key = self.entry.load_local_key()?;
         Ok(Arc::new(AskarLocalKey { key }))
     }
+
+    /// Sign with the stored key, or through the handler for an external key.
+    pub fn sign_message(
+        &self,
+        message: Vec<u8>,
+        sig_type: Option<String>,
+        handler: Option<Box<dyn AskarExternalKeyHandler>>,
+    ) -> Result<Vec<u8>, ErrorCode> {
+        match self.reference() {
+            AskarKeyReference::Local => Ok(self
+                .entry
+                .load_local_key()?
+                .sign_message(&message, sig_type.as_deref())?),
+            AskarKeyReference::External { id } => {
+                external_handler(&id, &handler)?.sign_message(id.clone(), message, sig_type)
+            }
+        }
+    }
+
+    pub fn verify_signature(
+        &self,
+        message: Vec<u8>,
+        signature: Vec<u8>,
+        sig_type: Option<String>,
+        handler: Option<Box<dyn AskarExternalKeyHandler>>,
+    ) -> Result<bool, ErrorCode> {
+        match self.reference() {
+            AskarKeyReference::Local => Ok(self.entry.load_local_key()?.verify_signature(
+                &message,
+                &signature,
+                sig_type.as_deref(),
+            )?),
+            AskarKeyReference::External { id } => external_handler(&id, &handler)?
+                .verify_signature(id.clone(), message, signature, sig_type),
+        }
+    }
+
+    pub fn to_key_exchange(
+        &self,
+        alg: AskarKeyAlg,
+        pk: Arc<AskarLocalKey>,
+        handler: Option<Box<dyn AskarExternalKeyHandler>>,
+    ) -> Result<Arc<AskarLocalKey>, ErrorCode> {
+        let alg: KeyAlg = alg.into();
+        let key = match self.reference() {
+            AskarKeyReference::Local => {
+                self.entry.load_local_key()?.to_key_exchange(alg, &pk.key)?
+            }
+            AskarKeyReference::External { id } => {
+                let secret = external_handler(&id, &handler)?.key_exchange(
+                    id.clone(),
+                    alg.into(),
+                    pk.key.to_jwk_public(None)?,
+                )?;
+                LocalKey::from_secret_bytes(alg, &secret)?
+            }
+        };
+        Ok(Arc::new(AskarLocalKey { key }))
+    }
 }
@@ -16,6 +16,14 @@ pub enum ErrorCode {
     Custom { message: String },
 }
 
+impl ErrorCode {
+    pub(crate) fn input(message: impl Into<String>) -> Self {
+        ErrorCode::Input {
+            message: message.into(),
+        }
+    }
+}
+
 impl From<Error> for ErrorCode {
     fn from(err: Error) -> ErrorCode {
         match err.kind() {
@@ -84,6 +92,14 @@ impl From<StorageError> for ErrorCode {
     }
 }
 
+impl From<uniffi::UnexpectedUniFFICallbackError> for ErrorCode {
+    fn from(err: uniffi::UnexpectedUniFFICallbackError) -> ErrorCode {
+        ErrorCode::Unexpected {
+            message: err.reason,
+        }
+    }
+}
+
 impl Display for ErrorCode {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         write!(f, "{:?}", self)
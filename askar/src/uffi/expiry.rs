@@ -0,0 +1,51 @@
+use crate::uffi::reserved::{is_tag, tag_value, EXPIRY_TAG};
+use aries_askar::storage::entry::{EntryTag, TagFilter};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub(crate) fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or_default()
+}
+
+pub(crate) fn format_timestamp(timestamp_ms: i64) -> String {
+    format!("{:020}", timestamp_ms.max(0))
+}
+
+/// The expiry time in milliseconds since the Unix epoch, if the entry expires.
+pub(crate) fn expiry_from_tags(tags: &[EntryTag]) -> Option<i64> {
+    tag_value(tags, EXPIRY_TAG).and_then(|value| value.parse().ok())
+}
+
+/// The time remaining until expiry, as passed to aries-askar when copying an entry.
+pub(crate) fn remaining_expiry_ms(tags: &[EntryTag]) -> Option<i64> {
+    expiry_from_tags(tags).map(|expiry| (expiry - now_ms()).max(1))
+}
+
+/// Replace the expiry tag of a tag set to record the given expiry, or no expiry.
+pub(crate) fn with_expiry_tag(
+    mut tags: Option<Vec<EntryTag>>,
+    expiry_ms: Option<i64>,
+) -> Option<Vec<EntryTag>> {
+    if let Some(tags) = tags.as_mut() {
+        tags.retain(|tag| !is_tag(tag, EXPIRY_TAG));
+    }
+    let Some(expiry_ms) = expiry_ms else {
+        return tags;
+    };
+    let mut tags = tags.unwrap_or_default();
+    tags.push(EntryTag::Plaintext(
+        EXPIRY_TAG.to_string(),
+        format_timestamp(now_ms().saturating_add(expiry_ms)),
+    ));
+    Some(tags)
+}
+
+/// Match entries which expire before the given number of milliseconds from now.
+pub(crate) fn expires_within_filter(duration_ms: i64) -> TagFilter {
+    TagFilter::is_lt(
+        format!("~{}", EXPIRY_TAG),
+        format_timestamp(now_ms().saturating_add(duration_ms)),
+    )
+}
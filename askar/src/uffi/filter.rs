@@ -0,0 +1,98 @@
+use crate::uffi::{error::ErrorCode, expiry::expires_within_filter};
+use aries_askar::storage::entry::TagFilter;
+use std::{str::FromStr, sync::Arc};
+
+pub struct AskarTagFilter {
+    pub filter: TagFilter,
+}
+
+impl AskarTagFilter {
+    fn wrap(filter: TagFilter) -> Arc<Self> {
+        Arc::new(Self { filter })
+    }
+
+    fn unwrap_all(filters: Vec<Arc<AskarTagFilter>>) -> Vec<TagFilter> {
+        filters.iter().map(|f| f.filter.clone()).collect()
+    }
+}
+
+#[uniffi::export]
+impl AskarTagFilter {
+    #[uniffi::constructor]
+    pub fn from_json(json: String) -> Result<Arc<Self>, ErrorCode> {
+        Ok(Self::wrap(TagFilter::from_str(&json)?))
+    }
+
+    #[uniffi::constructor]
+    pub fn is_eq(name: String, value: String) -> Arc<Self> {
+        Self::wrap(TagFilter::is_eq(name, value))
+    }
+
+    #[uniffi::constructor]
+    pub fn is_neq(name: String, value: String) -> Arc<Self> {
+        Self::wrap(TagFilter::is_not_eq(name, value))
+    }
+
+    #[uniffi::constructor]
+    pub fn is_gt(name: String, value: String) -> Arc<Self> {
+        Self::wrap(TagFilter::is_gt(name, value))
+    }
+
+    #[uniffi::constructor]
+    pub fn is_gte(name: String, value: String) -> Arc<Self> {
+        Self::wrap(TagFilter::is_gte(name, value))
+    }
+
+    #[uniffi::constructor]
+    pub fn is_lt(name: String, value: String) -> Arc<Self> {
+        Self::wrap(TagFilter::is_lt(name, value))
+    }
+
+    #[uniffi::constructor]
+    pub fn is_lte(name: String, value: String) -> Arc<Self> {
+        Self::wrap(TagFilter::is_lte(name, value))
+    }
+
+    #[uniffi::constructor]
+    pub fn is_like(name: String, value: String) -> Arc<Self> {
+        Self::wrap(TagFilter::is_like(name, value))
+    }
+
+    #[uniffi::constructor]
+    pub fn is_in(name: String, values: Vec<String>) -> Arc<Self> {
+        Self::wrap(TagFilter::is_in(name, values))
+    }
+
+    #[uniffi::constructor]
+    pub fn exist(names: Vec<String>) -> Arc<Self> {
+        Self::wrap(TagFilter::exist(names))
+    }
+
+    /// Match entries expiring within the given number of milliseconds.
+    /// Only expiry times recorded by this wrapper are matched; negate the filter
+    /// to exclude entries which are about to expire. The bound is fixed when the
+    /// filter is created, so create a new filter for each query.
+    #[uniffi::constructor]
+    pub fn expires_within(duration_ms: i64) -> Arc<Self> {
+        Self::wrap(expires_within_filter(duration_ms))
+    }
+
+    #[uniffi::constructor]
+    pub fn all_of(filters: Vec<Arc<AskarTagFilter>>) -> Arc<Self> {
+        Self::wrap(TagFilter::all_of(Self::unwrap_all(filters)))
+    }
+
+    #[uniffi::constructor]
+    pub fn any_of(filters: Vec<Arc<AskarTagFilter>>) -> Arc<Self> {
+        Self::wrap(TagFilter::any_of(Self::unwrap_all(filters)))
+    }
+
+    #[uniffi::constructor]
+    pub fn negate(filter: Arc<AskarTagFilter>) -> Arc<Self> {
+        Self::wrap(TagFilter::negate(filter.filter.clone()))
+    }
+
+    pub fn to_json(&self) -> Result<String, ErrorCode> {
+        Ok(self.filter.to_string()?)
+    }
+}
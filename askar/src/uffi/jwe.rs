@@ -0,0 +1,467 @@
+use crate::uffi::{
+    encoding::{b64_url_decode, b64_url_encode_no_pad},
+    error::ErrorCode,
+    key::AskarLocalKey,
+};
+use aries_askar::{
+    crypto::alg::{AesTypes, Chacha20Types, KeyAlg},
+    kms::{derive_key_ecdh_1pu, derive_key_ecdh_es, LocalKey},
+};
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+
+const WRAP_ALG: KeyAlg = KeyAlg::Aes(AesTypes::A256Kw);
+
+#[derive(uniffi::Enum, Clone, Copy, PartialEq)]
+pub enum AskarJweAlg {
+    EcdhEsA256kw,
+    Ecdh1puA256kw,
+}
+
+impl AskarJweAlg {
+    fn as_str(&self) -> &'static str {
+        match self {
+            AskarJweAlg::EcdhEsA256kw => "ECDH-ES+A256KW",
+            AskarJweAlg::Ecdh1puA256kw => "ECDH-1PU+A256KW",
+        }
+    }
+
+    fn parse(alg: &str) -> Result<Self, ErrorCode> {
+        match alg {
+            "ECDH-ES+A256KW" => Ok(AskarJweAlg::EcdhEsA256kw),
+            "ECDH-1PU+A256KW" => Ok(AskarJweAlg::Ecdh1puA256kw),
+            _ => Err(ErrorCode::Unsupported {
+                message: format!("Unsupported JWE algorithm: {}", alg),
+            }),
+        }
+    }
+}
+
+#[derive(uniffi::Enum, Clone, Copy, PartialEq)]
+pub enum AskarJweEnc {
+    A256CbcHs512,
+    A256Gcm,
+    XC20P,
+}
+
+impl AskarJweEnc {
+    fn as_str(&self) -> &'static str {
+        match self {
+            AskarJweEnc::A256CbcHs512 => "A256CBC-HS512",
+            AskarJweEnc::A256Gcm => "A256GCM",
+            AskarJweEnc::XC20P => "XC20P",
+        }
+    }
+
+    fn parse(enc: &str) -> Result<Self, ErrorCode> {
+        match enc {
+            "A256CBC-HS512" => Ok(AskarJweEnc::A256CbcHs512),
+            "A256GCM" => Ok(AskarJweEnc::A256Gcm),
+            "XC20P" => Ok(AskarJweEnc::XC20P),
+            _ => Err(ErrorCode::Unsupported {
+                message: format!("Unsupported JWE content encryption: {}", enc),
+            }),
+        }
+    }
+
+    fn key_alg(&self) -> KeyAlg {
+        match self {
+            AskarJweEnc::A256CbcHs512 => KeyAlg::Aes(AesTypes::A256CbcHs512),
+            AskarJweEnc::A256Gcm => KeyAlg::Aes(AesTypes::A256Gcm),
+            AskarJweEnc::XC20P => KeyAlg::Chacha20(Chacha20Types::XC20P),
+        }
+    }
+}
+
+#[derive(uniffi::Record)]
+pub struct AskarJweRecipient {
+    pub kid: String,
+    pub key: Arc<AskarLocalKey>,
+}
+
+struct JweRecipient {
+    kid: Option<String>,
+    encrypted_key: Vec<u8>,
+}
+
+struct JweParts {
+    protected: String,
+    header: Map<String, Value>,
+    recipients: Vec<JweRecipient>,
+    iv: Vec<u8>,
+    ciphertext: Vec<u8>,
+    tag: Vec<u8>,
+}
+
+fn header_str<'h>(header: &'h Map<String, Value>, name: &str) -> Option<&'h str> {
+    header.get(name).and_then(Value::as_str)
+}
+
+fn decode_field(value: &Value, name: &str) -> Result<Vec<u8>, ErrorCode> {
+    let value = value
+        .get(name)
+        .and_then(Value::as_str)
+        .ok_or_else(|| ErrorCode::input(format!("Missing JWE field: {}", name)))?;
+    b64_url_decode(value)
+}
+
+fn parse_jwe(jwe: &str) -> Result<JweParts, ErrorCode> {
+    let jwe = jwe.trim();
+    let (protected, recipients, iv, ciphertext, tag) = if jwe.starts_with('{') {
+        let value: Value = serde_json::from_str(jwe)
+            .map_err(|err| ErrorCode::input(format!("Invalid JWE: {}", err)))?;
+        let protected = value
+            .get("protected")
+            .and_then(Value::as_str)
+            .ok_or_else(|| ErrorCode::input("Missing JWE field: protected"))?
+            .to_string();
+        let parse_recipient = |recipient: &Value| -> Result<JweRecipient, ErrorCode> {
+            Ok(JweRecipient {
+                kid: recipient
+                    .get("header")
+                    .and_then(|header| header.get("kid"))
+                    .and_then(Value::as_str)
+                    .map(String::from),
+                encrypted_key: decode_field(recipient, "encrypted_key")?,
+            })
+        };
+        let recipients = match value.get("recipients").and_then(Value::as_array) {
+            Some(recipients) => recipients
+                .iter()
+                .map(parse_recipient)
+                .collect::<Result<Vec<_>, _>>()?,
+            // Flattened JSON serialization
+            None => vec![parse_recipient(&value)?],
+        };
+        (
+            protected,
+            recipients,
+            decode_field(&value, "iv")?,
+            decode_field(&value, "ciphertext")?,
+            decode_field(&value, "tag")?,
+        )
+    } else {
+        let parts: Vec<&str> = jwe.split('.').collect();
+        if parts.len() != 5 {
+            return Err(ErrorCode::input("Invalid compact JWE"));
+        }
+        let recipient = JweRecipient {
+            kid: None,
+            encrypted_key: b64_url_decode(parts[1])?,
+        };
+        (
+            parts[0].to_string(),
+            vec![recipient],
+            b64_url_decode(parts[2])?,
+            b64_url_decode(parts[3])?,
+            b64_url_decode(parts[4])?,
+        )
+    };
+    let header = match serde_json::from_slice(&b64_url_decode(&protected)?) {
+        Ok(Value::Object(header)) => header,
+        _ => return Err(ErrorCode::input("Invalid JWE protected header")),
+    };
+    Ok(JweParts {
+        protected,
+        header,
+        recipients,
+        iv,
+        ciphertext,
+        tag,
+    })
+}
+
+// The key agreement inputs shared by every recipient of a JWE
+struct KeyAgreement<'a> {
+    alg: AskarJweAlg,
+    ephemeral_key: &'a LocalKey,
+    sender_key: Option<&'a LocalKey>,
+    apu: &'a [u8],
+    apv: &'a [u8],
+}
+
+impl KeyAgreement<'_> {
+    fn derive_kek(
+        &self,
+        recipient_key: &LocalKey,
+        cc_tag: &[u8],
+        receive: bool,
+    ) -> Result<LocalKey, ErrorCode> {
+        let alg_id = self.alg.as_str().as_bytes();
+        let kek = match self.alg {
+            AskarJweAlg::EcdhEsA256kw => derive_key_ecdh_es(
+                WRAP_ALG,
+                self.ephemeral_key,
+                recipient_key,
+                alg_id,
+                self.apu,
+                self.apv,
+                receive,
+            )?,
+            AskarJweAlg::Ecdh1puA256kw => derive_key_ecdh_1pu(
+                WRAP_ALG,
+                self.ephemeral_key,
+                self.sender_key
+                    .ok_or_else(|| ErrorCode::input("ECDH-1PU requires a sender key"))?,
+                recipient_key,
+                alg_id,
+                self.apu,
+                self.apv,
+                cc_tag,
+                receive,
+            )?,
+        };
+        Ok(kek)
+    }
+}
+
+struct EncryptedJwe {
+    protected: String,
+    encrypted_keys: Vec<Vec<u8>>,
+    iv: Vec<u8>,
+    ciphertext: Vec<u8>,
+    tag: Vec<u8>,
+}
+
+// ECDH-1PU key wrapping is only defined for AES-CBC-HMAC content encryption
+fn check_alg_enc(alg: AskarJweAlg, enc: AskarJweEnc) -> Result<(), ErrorCode> {
+    if alg == AskarJweAlg::Ecdh1puA256kw && enc != AskarJweEnc::A256CbcHs512 {
+        return Err(ErrorCode::Unsupported {
+            message: format!(
+                "{} requires {}",
+                alg.as_str(),
+                AskarJweEnc::A256CbcHs512.as_str()
+            ),
+        });
+    }
+    Ok(())
+}
+
+fn encrypt_parts(
+    plaintext: &[u8],
+    alg: AskarJweAlg,
+    enc: AskarJweEnc,
+    recipients: &[AskarJweRecipient],
+    sender_kid: Option<String>,
+    sender_key: Option<Arc<AskarLocalKey>>,
+    compact: bool,
+) -> Result<EncryptedJwe, ErrorCode> {
+    let first = recipients
+        .first()
+        .ok_or_else(|| ErrorCode::input("No JWE recipients"))?;
+    let key_alg = first.key.key.algorithm();
+    if recipients.iter().any(|r| r.key.key.algorithm() != key_alg) {
+        return Err(ErrorCode::input(
+            "JWE recipients must use the same key type",
+        ));
+    }
+    if alg == AskarJweAlg::Ecdh1puA256kw && (sender_key.is_none() || sender_kid.is_none()) {
+        return Err(ErrorCode::input(
+            "ECDH-1PU requires a sender key and key id",
+        ));
+    }
+    check_alg_enc(alg, enc)?;
+
+    let ephemeral_key = LocalKey::generate_with_rng(key_alg, true)?;
+    let epk: Value = serde_json::from_str(&ephemeral_key.to_jwk_public(None)?).map_err(|err| {
+        ErrorCode::Unexpected {
+            message: format!("Error encoding ephemeral key: {}", err),
+        }
+    })?;
+    let mut kids: Vec<&str> = recipients.iter().map(|r| r.kid.as_str()).collect();
+    kids.sort_unstable();
+    let apv = Sha256::digest(kids.join(".").as_bytes()).to_vec();
+    let apu = match alg {
+        AskarJweAlg::Ecdh1puA256kw => sender_kid.clone().unwrap_or_default().into_bytes(),
+        AskarJweAlg::EcdhEsA256kw => vec![],
+    };
+
+    let mut header = json!({
+        "alg": alg.as_str(),
+        "enc": enc.as_str(),
+        "apv": b64_url_encode_no_pad(&apv),
+        "epk": epk,
+    });
+    if alg == AskarJweAlg::Ecdh1puA256kw {
+        header["apu"] = json!(b64_url_encode_no_pad(&apu));
+        header["skid"] = json!(sender_kid);
+    }
+    if compact {
+        header["kid"] = json!(first.kid);
+    }
+    let protected = b64_url_encode_no_pad(header.to_string().as_bytes());
+
+    let cek = LocalKey::generate_with_rng(enc.key_alg(), true)?;
+    let nonce = cek.aead_random_nonce()?;
+    let encrypted = cek.aead_encrypt(plaintext, &nonce, protected.as_bytes())?;
+
+    let agreement = KeyAgreement {
+        alg,
+        ephemeral_key: &ephemeral_key,
+        sender_key: sender_key.as_ref().map(|k| &k.key),
+        apu: &apu,
+        apv: &apv,
+    };
+    let mut encrypted_keys = Vec::with_capacity(recipients.len());
+    for recipient in recipients {
+        let kek = agreement.derive_kek(&recipient.key.key, encrypted.tag(), false)?;
+        encrypted_keys.push(kek.wrap_key(&cek, &[])?.ciphertext().to_vec());
+    }
+    Ok(EncryptedJwe {
+        protected,
+        encrypted_keys,
+        iv: encrypted.nonce().to_vec(),
+        ciphertext: encrypted.ciphertext().to_vec(),
+        tag: encrypted.tag().to_vec(),
+    })
+}
+
+/// JSON Web Encryption with ECDH-ES and ECDH-1PU key agreement and A256KW
+/// key wrapping, as used for DIDComm v2 anoncrypt and authcrypt messages.
+pub struct AskarJwe {}
+
+impl AskarJwe {
+    pub fn new() -> Self {
+        Self {}
+    }
+}
+
+#[uniffi::export]
+impl AskarJwe {
+    pub fn encrypt_json(
+        &self,
+        plaintext: Vec<u8>,
+        alg: AskarJweAlg,
+        enc: AskarJweEnc,
+        recipients: Vec<AskarJweRecipient>,
+        sender_kid: Option<String>,
+        sender_key: Option<Arc<AskarLocalKey>>,
+    ) -> Result<String, ErrorCode> {
+        let encrypted = encrypt_parts(
+            &plaintext,
+            alg,
+            enc,
+            &recipients,
+            sender_kid,
+            sender_key,
+            false,
+        )?;
+        let recipients: Vec<Value> = recipients
+            .iter()
+            .zip(encrypted.encrypted_keys)
+            .map(|(recipient, encrypted_key)| {
+                json!({
+                    "header": { "kid": recipient.kid },
+                    "encrypted_key": b64_url_encode_no_pad(&encrypted_key),
+                })
+            })
+            .collect();
+        Ok(json!({
+            "protected": encrypted.protected,
+            "recipients": recipients,
+            "iv": b64_url_encode_no_pad(&encrypted.iv),
+            "ciphertext": b64_url_encode_no_pad(&encrypted.ciphertext),
+            "tag": b64_url_encode_no_pad(&encrypted.tag),
+        })
+        .to_string())
+    }
+
+    pub fn encrypt_compact(
+        &self,
+        plaintext: Vec<u8>,
+        alg: AskarJweAlg,
+        enc: AskarJweEnc,
+        recipient: AskarJweRecipient,
+        sender_kid: Option<String>,
+        sender_key: Option<Arc<AskarLocalKey>>,
+    ) -> Result<String, ErrorCode> {
+        let encrypted = encrypt_parts(
+            &plaintext,
+            alg,
+            enc,
+            &[recipient],
+            sender_kid,
+            sender_key,
+            true,
+        )?;
+        Ok(format!(
+            "{}.{}.{}.{}.{}",
+            encrypted.protected,
+            b64_url_encode_no_pad(&encrypted.encrypted_keys[0]),
+            b64_url_encode_no_pad(&encrypted.iv),
+            b64_url_encode_no_pad(&encrypted.ciphertext),
+            b64_url_encode_no_pad(&encrypted.tag),
+        ))
+    }
+
+    pub fn protected_header(&self, jwe: String) -> Result<String, ErrorCode> {
+        Ok(Value::Object(parse_jwe(&jwe)?.header).to_string())
+    }
+
+    pub fn recipient_kids(&self, jwe: String) -> Result<Vec<String>, ErrorCode> {
+        let parts = parse_jwe(&jwe)?;
+        let kids = parts
+            .recipients
+            .into_iter()
+            .filter_map(|recipient| recipient.kid)
+            .chain(header_str(&parts.header, "kid").map(String::from))
+            .collect();
+        Ok(kids)
+    }
+
+    pub fn decrypt(
+        &self,
+        jwe: String,
+        recipient: AskarJweRecipient,
+        sender_key: Option<Arc<AskarLocalKey>>,
+    ) -> Result<Vec<u8>, ErrorCode> {
+        let parts = parse_jwe(&jwe)?;
+        let header = &parts.header;
+        let alg = AskarJweAlg::parse(header_str(header, "alg").unwrap_or_default())?;
+        let enc = AskarJweEnc::parse(header_str(header, "enc").unwrap_or_default())?;
+        check_alg_enc(alg, enc)?;
+        let epk = header
+            .get("epk")
+            .ok_or_else(|| ErrorCode::input("Missing JWE header: epk"))?;
+        let ephemeral_key = LocalKey::from_jwk(&epk.to_string())?;
+        let apu = header_str(header, "apu")
+            .map(b64_url_decode)
+            .transpose()?
+            .unwrap_or_default();
+        let apv = header_str(header, "apv")
+            .map(b64_url_decode)
+            .transpose()?
+            .unwrap_or_default();
+
+        let encrypted_key = match parts.recipients.as_slice() {
+            [single] if single.kid.is_none() => &single.encrypted_key,
+            recipients => {
+                &recipients
+                    .iter()
+                    .find(|r| r.kid.as_deref() == Some(recipient.kid.as_str()))
+                    .ok_or(ErrorCode::NotFound {
+                        message: format!("No JWE recipient found for kid: {}", recipient.kid),
+                    })?
+                    .encrypted_key
+            }
+        };
+
+        let agreement = KeyAgreement {
+            alg,
+            ephemeral_key: &ephemeral_key,
+            sender_key: sender_key.as_ref().map(|k| &k.key),
+            apu: &apu,
+            apv: &apv,
+        };
+        let kek = agreement.derive_kek(&recipient.key.key, &parts.tag, true)?;
+        let no_tag: &[u8] = &[];
+        let cek = kek.unwrap_key(enc.key_alg(), (encrypted_key.as_slice(), no_tag), &[])?;
+        let plaintext = cek.aead_decrypt(
+            (parts.ciphertext.as_slice(), parts.tag.as_slice()),
+            &parts.iv,
+            parts.protected.as_bytes(),
+        )?;
+        Ok(plaintext.to_vec())
+    }
+}
@@ -0,0 +1,170 @@
+use crate::uffi::{
+    encoding::{b64_url_decode, b64_url_encode_no_pad},
+    error::ErrorCode,
+    key::AskarLocalKey,
+};
+use aries_askar::{
+    crypto::alg::{EcCurves, KeyAlg},
+    kms::LocalKey,
+};
+use serde_json::{json, Map, Value};
+use std::sync::Arc;
+
+fn jws_alg(key: &LocalKey) -> Result<&'static str, ErrorCode> {
+    match key.algorithm() {
+        KeyAlg::Ed25519 => Ok("EdDSA"),
+        KeyAlg::EcCurve(EcCurves::Secp256r1) => Ok("ES256"),
+        KeyAlg::EcCurve(EcCurves::Secp256k1) => Ok("ES256K"),
+        KeyAlg::EcCurve(EcCurves::Secp384r1) => Ok("ES384"),
+        alg => Err(ErrorCode::Unsupported {
+            message: format!("Unsupported key algorithm for JWS: {}", alg.as_str()),
+        }),
+    }
+}
+
+struct JwsParts {
+    protected: String,
+    header: Map<String, Value>,
+    payload: Option<String>,
+    signature: Vec<u8>,
+}
+
+fn parse_jws(jws: &str) -> Result<JwsParts, ErrorCode> {
+    let jws = jws.trim();
+    let (protected, payload, signature) = if jws.starts_with('{') {
+        let value: Value = serde_json::from_str(jws)
+            .map_err(|err| ErrorCode::input(format!("Invalid JWS: {}", err)))?;
+        let field = |name: &str| value.get(name).and_then(Value::as_str).map(String::from);
+        (
+            field("protected").ok_or_else(|| ErrorCode::input("Missing JWS field: protected"))?,
+            field("payload"),
+            field("signature").ok_or_else(|| ErrorCode::input("Missing JWS field: signature"))?,
+        )
+    } else {
+        let parts: Vec<&str> = jws.split('.').collect();
+        if parts.len() != 3 {
+            return Err(ErrorCode::input("Invalid compact JWS"));
+        }
+        let payload = Some(parts[1].to_string()).filter(|payload| !payload.is_empty());
+        (parts[0].to_string(), payload, parts[2].to_string())
+    };
+    let header = match serde_json::from_slice(&b64_url_decode(&protected)?) {
+        Ok(Value::Object(header)) => header,
+        _ => return Err(ErrorCode::input("Invalid JWS protected header")),
+    };
+    Ok(JwsParts {
+        protected,
+        header,
+        payload,
+        signature: b64_url_decode(&signature)?,
+    })
+}
+
+/// JSON Web Signatures over `AskarLocalKey::sign_message`, with the `alg`
+/// header chosen from the key algorithm.
+pub struct AskarJws {}
+
+impl AskarJws {
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    fn sign_parts(
+        &self,
+        payload: &[u8],
+        key: &LocalKey,
+        kid: Option<String>,
+    ) -> Result<(String, String, String), ErrorCode> {
+        let mut header = json!({ "alg": jws_alg(key)? });
+        if let Some(kid) = kid {
+            header["kid"] = json!(kid);
+        }
+        let protected = b64_url_encode_no_pad(header.to_string().as_bytes());
+        let payload = b64_url_encode_no_pad(payload);
+        let signature = key.sign_message(format!("{}.{}", protected, payload).as_bytes(), None)?;
+        Ok((protected, payload, b64_url_encode_no_pad(&signature)))
+    }
+
+    fn verify_parts(
+        &self,
+        jws: &str,
+        key: &LocalKey,
+        detached_payload: Option<Vec<u8>>,
+    ) -> Result<bool, ErrorCode> {
+        let parts = parse_jws(jws)?;
+        let alg = parts.header.get("alg").and_then(Value::as_str);
+        if alg != Some(jws_alg(key)?) {
+            return Ok(false);
+        }
+        let payload = match (detached_payload, parts.payload) {
+            (Some(payload), _) => b64_url_encode_no_pad(&payload),
+            (None, Some(payload)) => payload,
+            (None, None) => return Err(ErrorCode::input("Missing payload for detached JWS")),
+        };
+        let signing_input = format!("{}.{}", parts.protected, payload);
+        Ok(key.verify_signature(signing_input.as_bytes(), &parts.signature, None)?)
+    }
+}
+
+#[uniffi::export]
+impl AskarJws {
+    pub fn sign_compact(
+        &self,
+        payload: Vec<u8>,
+        key: Arc<AskarLocalKey>,
+        kid: Option<String>,
+        detached: bool,
+    ) -> Result<String, ErrorCode> {
+        let (protected, payload, signature) = self.sign_parts(&payload, &key.key, kid)?;
+        let payload = if detached { String::new() } else { payload };
+        Ok(format!("{}.{}.{}", protected, payload, signature))
+    }
+
+    pub fn sign_flattened(
+        &self,
+        payload: Vec<u8>,
+        key: Arc<AskarLocalKey>,
+        kid: Option<String>,
+        detached: bool,
+    ) -> Result<String, ErrorCode> {
+        let (protected, payload, signature) = self.sign_parts(&payload, &key.key, kid)?;
+        let mut jws = json!({
+            "protected": protected,
+            "signature": signature,
+        });
+        if !detached {
+            jws["payload"] = json!(payload);
+        }
+        Ok(jws.to_string())
+    }
+
+    pub fn verify(
+        &self,
+        jws: String,
+        key: Arc<AskarLocalKey>,
+        detached_payload: Option<Vec<u8>>,
+    ) -> Result<bool, ErrorCode> {
+        self.verify_parts(&jws, &key.key, detached_payload)
+    }
+
+    pub fn verify_with_jwk(
+        &self,
+        jws: String,
+        jwk: String,
+        detached_payload: Option<Vec<u8>>,
+    ) -> Result<bool, ErrorCode> {
+        let key = LocalKey::from_jwk(&jwk)?;
+        self.verify_parts(&jws, &key, detached_payload)
+    }
+
+    pub fn protected_header(&self, jws: String) -> Result<String, ErrorCode> {
+        Ok(Value::Object(parse_jws(&jws)?.header).to_string())
+    }
+
+    pub fn payload(&self, jws: String) -> Result<Option<Vec<u8>>, ErrorCode> {
+        parse_jws(&jws)?
+            .payload
+            .map(|payload| b64_url_decode(&payload))
+            .transpose()
+    }
+}
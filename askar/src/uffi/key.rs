@@ -1,8 +1,20 @@
-use crate::uffi::error::ErrorCode;
+use crate::uffi::{
+    encoding::{b58_decode, b58_encode, b64_url_decode, b64_url_encode_no_pad},
+    error::ErrorCode,
+    stream::{decrypt_file, encrypt_file},
+};
 use aries_askar::{
-    crypto::alg::{AesTypes, BlsCurves, Chacha20Types, EcCurves, KeyAlg},
+    crypto::{
+        alg::{AesTypes, BlsCurves, Chacha20Types, EcCurves, KeyAlg},
+        kdf::{
+            argon2::{Argon2, Params, PARAMS_INTERACTIVE, PARAMS_MODERATE, SALT_LENGTH},
+            KeyDerivation,
+        },
+        random::fill_random,
+    },
     kms::{Encrypted, LocalKey},
 };
+use serde_json::{json, Map, Value};
 use std::sync::Arc;
 
 #[derive(uniffi::Enum)]
@@ -71,6 +83,263 @@ impl From<KeyAlg> for AskarKeyAlg {
     }
 }
 
+const DID_KEY_PREFIX: &str = "did:key:";
+
+// Multicodec prefixes of public keys, as unsigned varints
+const MULTICODEC_PREFIXES: [(KeyAlg, [u8; 2]); 8] = [
+    (KeyAlg::Ed25519, [0xed, 0x01]),
+    (KeyAlg::X25519, [0xec, 0x01]),
+    (KeyAlg::EcCurve(EcCurves::Secp256r1), [0x80, 0x24]),
+    (KeyAlg::EcCurve(EcCurves::Secp384r1), [0x81, 0x24]),
+    (KeyAlg::EcCurve(EcCurves::Secp256k1), [0xe7, 0x01]),
+    (KeyAlg::Bls12_381(BlsCurves::G1), [0xea, 0x01]),
+    (KeyAlg::Bls12_381(BlsCurves::G2), [0xeb, 0x01]),
+    (KeyAlg::Bls12_381(BlsCurves::G1G2), [0xee, 0x01]),
+];
+
+fn encode_multibase_public(key: &LocalKey) -> Result<String, ErrorCode> {
+    let alg = key.algorithm();
+    let (_, prefix) = MULTICODEC_PREFIXES
+        .iter()
+        .find(|(key_alg, _)| *key_alg == alg)
+        .ok_or(ErrorCode::Unsupported {
+            message: format!("No multicodec prefix for key algorithm: {}", alg.as_str()),
+        })?;
+    let bytes = [&prefix[..], &key.to_public_bytes()?].concat();
+    Ok(format!("z{}", b58_encode(&bytes)))
+}
+
+fn decode_multibase_public(value: &str) -> Result<LocalKey, ErrorCode> {
+    let encoded = value.strip_prefix('z').ok_or(ErrorCode::Unsupported {
+        message: String::from("Only base58btc multibase values are supported"),
+    })?;
+    let bytes = b58_decode(encoded)?;
+    let (alg, _) = MULTICODEC_PREFIXES
+        .iter()
+        .find(|(_, prefix)| bytes.starts_with(prefix))
+        .ok_or(ErrorCode::Unsupported {
+            message: String::from("Unsupported multicodec key type"),
+        })?;
+    Ok(LocalKey::from_public_bytes(*alg, &bytes[2..])?)
+}
+
+fn decode_did_key(did: &str) -> Result<LocalKey, ErrorCode> {
+    let id = did.strip_prefix(DID_KEY_PREFIX).ok_or(ErrorCode::Input {
+        message: format!("Not a did:key identifier: {}", did),
+    })?;
+    let id = id.split('#').next().unwrap_or_default();
+    decode_multibase_public(id)
+}
+
+fn ed25519_public_bytes(key: &LocalKey) -> Result<Vec<u8>, ErrorCode> {
+    if key.algorithm() != KeyAlg::Ed25519 {
+        return Err(ErrorCode::Unsupported {
+            message: String::from("Indy verkeys and DIDs require an Ed25519 key"),
+        });
+    }
+    Ok(key.to_public_bytes()?.into_vec())
+}
+
+fn decode_indy_verkey(verkey: &str, did: Option<&str>) -> Result<Vec<u8>, ErrorCode> {
+    // Verkeys may carry a crypto type suffix, which is always ed25519
+    let verkey = verkey.strip_suffix(":ed25519").unwrap_or(verkey);
+    match verkey.strip_prefix('~') {
+        Some(abbreviated) => {
+            let did = did.ok_or(ErrorCode::Input {
+                message: String::from("A DID is required to resolve an abbreviated verkey"),
+            })?;
+            let did = did.rsplit(':').next().unwrap_or(did);
+            let bytes = [b58_decode(did)?, b58_decode(abbreviated)?].concat();
+            Ok(bytes)
+        }
+        None => b58_decode(verkey),
+    }
+}
+
+const PASSPHRASE_VERSION: u64 = 1;
+const PASSPHRASE_ALG: &str = "ARGON2I+A256KW";
+const KEY_EXPORT_CTY: &str = "jwk+json";
+
+#[derive(uniffi::Enum, Clone, Copy)]
+pub enum AskarKdfParams {
+    Argon2iInteractive,
+    Argon2iModerate,
+}
+
+impl AskarKdfParams {
+    fn as_str(&self) -> &'static str {
+        match self {
+            AskarKdfParams::Argon2iInteractive => "interactive",
+            AskarKdfParams::Argon2iModerate => "moderate",
+        }
+    }
+
+    fn parse(level: &str) -> Result<Self, ErrorCode> {
+        match level {
+            "interactive" => Ok(AskarKdfParams::Argon2iInteractive),
+            "moderate" => Ok(AskarKdfParams::Argon2iModerate),
+            _ => Err(ErrorCode::Unsupported {
+                message: format!("Unsupported passphrase KDF parameters: {}", level),
+            }),
+        }
+    }
+
+    fn params(&self) -> Params {
+        match self {
+            AskarKdfParams::Argon2iInteractive => PARAMS_INTERACTIVE,
+            AskarKdfParams::Argon2iModerate => PARAMS_MODERATE,
+        }
+    }
+}
+
+fn export_enc_name(alg: KeyAlg) -> Result<&'static str, ErrorCode> {
+    match alg {
+        KeyAlg::Aes(AesTypes::A128Gcm) => Ok("A128GCM"),
+        KeyAlg::Aes(AesTypes::A256Gcm) => Ok("A256GCM"),
+        KeyAlg::Aes(AesTypes::A128CbcHs256) => Ok("A128CBC-HS256"),
+        KeyAlg::Aes(AesTypes::A256CbcHs512) => Ok("A256CBC-HS512"),
+        KeyAlg::Chacha20(Chacha20Types::C20P) => Ok("C20P"),
+        KeyAlg::Chacha20(Chacha20Types::XC20P) => Ok("XC20P"),
+        _ => Err(ErrorCode::Unsupported {
+            message: format!("Not an AEAD algorithm: {}", alg.as_str()),
+        }),
+    }
+}
+
+fn export_enc_alg(enc: &str) -> Result<KeyAlg, ErrorCode> {
+    match enc {
+        "A128GCM" => Ok(KeyAlg::Aes(AesTypes::A128Gcm)),
+        "A256GCM" => Ok(KeyAlg::Aes(AesTypes::A256Gcm)),
+        "A128CBC-HS256" => Ok(KeyAlg::Aes(AesTypes::A128CbcHs256)),
+        "A256CBC-HS512" => Ok(KeyAlg::Aes(AesTypes::A256CbcHs512)),
+        "C20P" => Ok(KeyAlg::Chacha20(Chacha20Types::C20P)),
+        "XC20P" => Ok(KeyAlg::Chacha20(Chacha20Types::XC20P)),
+        _ => Err(ErrorCode::Unsupported {
+            message: format!("Unsupported passphrase encryption: {}", enc),
+        }),
+    }
+}
+
+pub(crate) fn derive_argon2_key(
+    passphrase: &str,
+    salt: &[u8],
+    kdf_params: AskarKdfParams,
+    output: &mut [u8],
+) -> Result<(), ErrorCode> {
+    Argon2::new(passphrase.as_bytes(), salt, kdf_params.params())
+        .and_then(|mut kdf| kdf.derive_key_bytes(output))
+        .map_err(|err| ErrorCode::Encryption {
+            message: format!("Error deriving passphrase key: {}", err),
+        })
+}
+
+fn derive_passphrase_kek(
+    passphrase: &str,
+    salt: &[u8],
+    kdf_params: AskarKdfParams,
+) -> Result<LocalKey, ErrorCode> {
+    let mut kek = [0u8; 32];
+    derive_argon2_key(passphrase, salt, kdf_params, &mut kek)?;
+    Ok(LocalKey::from_secret_bytes(
+        KeyAlg::Aes(AesTypes::A256Kw),
+        &kek,
+    )?)
+}
+
+// Passphrase-encrypted blobs are compact JWEs in the style of PBES2 (RFC 7518 section 4.8),
+// with Argon2i in place of PBKDF2 to derive the key wrapping key
+pub(crate) fn passphrase_encrypt(
+    plaintext: &[u8],
+    cty: &str,
+    passphrase: &str,
+    kdf_params: AskarKdfParams,
+    enc: KeyAlg,
+) -> Result<String, ErrorCode> {
+    let mut salt = [0u8; SALT_LENGTH];
+    fill_random(&mut salt);
+    let header = json!({
+        "alg": PASSPHRASE_ALG,
+        "enc": export_enc_name(enc)?,
+        "cty": cty,
+        "ver": PASSPHRASE_VERSION,
+        "p2s": b64_url_encode_no_pad(&salt),
+        "p2l": kdf_params.as_str(),
+    });
+    let protected = b64_url_encode_no_pad(header.to_string().as_bytes());
+
+    let kek = derive_passphrase_kek(passphrase, &salt, kdf_params)?;
+    let cek = LocalKey::generate_with_rng(enc, true)?;
+    let encrypted_key = kek.wrap_key(&cek, &[])?;
+    let nonce = cek.aead_random_nonce()?;
+    let encrypted = cek.aead_encrypt(plaintext, &nonce, protected.as_bytes())?;
+    Ok([
+        protected,
+        b64_url_encode_no_pad(encrypted_key.ciphertext()),
+        b64_url_encode_no_pad(encrypted.nonce()),
+        b64_url_encode_no_pad(encrypted.ciphertext()),
+        b64_url_encode_no_pad(encrypted.tag()),
+    ]
+    .join("."))
+}
+
+pub(crate) fn passphrase_decrypt(
+    blob: &str,
+    cty: &str,
+    passphrase: &str,
+) -> Result<Vec<u8>, ErrorCode> {
+    let parts: Vec<&str> = blob.trim().split('.').collect();
+    let [protected, encrypted_key, iv, ciphertext, tag] = parts.as_slice() else {
+        return Err(ErrorCode::input("Invalid passphrase-encrypted data"));
+    };
+    let header: Map<String, Value> = serde_json::from_slice(&b64_url_decode(protected)?)
+        .map_err(|_| ErrorCode::input("Invalid passphrase-encrypted data header"))?;
+    let field = |name: &str| {
+        header.get(name).and_then(Value::as_str).ok_or_else(|| {
+            ErrorCode::input(format!("Missing passphrase-encrypted header: {}", name))
+        })
+    };
+    match header.get("ver").and_then(Value::as_u64) {
+        Some(PASSPHRASE_VERSION) => (),
+        Some(ver) => {
+            return Err(ErrorCode::Unsupported {
+                message: format!("Unsupported passphrase-encrypted version: {}", ver),
+            })
+        }
+        None => return Err(ErrorCode::input("Missing passphrase-encrypted header: ver")),
+    }
+    if field("alg")? != PASSPHRASE_ALG {
+        return Err(ErrorCode::Unsupported {
+            message: format!("Unsupported passphrase algorithm: {}", field("alg")?),
+        });
+    }
+    if field("cty")? != cty {
+        return Err(ErrorCode::input(format!(
+            "Unexpected passphrase-encrypted content type: {}",
+            field("cty")?
+        )));
+    }
+    let enc = export_enc_alg(field("enc")?)?;
+    let kdf_params = AskarKdfParams::parse(field("p2l")?)?;
+    let salt = b64_url_decode(field("p2s")?)?;
+
+    let kek = derive_passphrase_kek(passphrase, &salt, kdf_params)?;
+    let no_tag: &[u8] = &[];
+    let cek = kek.unwrap_key(
+        enc,
+        (b64_url_decode(encrypted_key)?.as_slice(), no_tag),
+        &[],
+    )?;
+    let plaintext = cek.aead_decrypt(
+        (
+            b64_url_decode(ciphertext)?.as_slice(),
+            b64_url_decode(tag)?.as_slice(),
+        ),
+        &b64_url_decode(iv)?,
+        protected.as_bytes(),
+    )?;
+    Ok(plaintext.to_vec())
+}
+
 #[derive(uniffi::Enum)]
 pub enum SeedMethod {
     BlsKeyGen,
@@ -173,6 +442,50 @@ impl LocalKeyFactory {
         let key = LocalKey::from_secret_bytes(alg.into(), &bytes)?;
         Ok(Arc::new(AskarLocalKey { key }))
     }
+
+    pub fn from_multibase(&self, value: String) -> Result<Arc<AskarLocalKey>, ErrorCode> {
+        let key = decode_multibase_public(&value)?;
+        Ok(Arc::new(AskarLocalKey { key }))
+    }
+
+    pub fn from_indy_verkey(
+        &self,
+        verkey: String,
+        did: Option<String>,
+    ) -> Result<Arc<AskarLocalKey>, ErrorCode> {
+        let bytes = decode_indy_verkey(&verkey, did.as_deref())?;
+        let key = LocalKey::from_public_bytes(KeyAlg::Ed25519, &bytes)?;
+        Ok(Arc::new(AskarLocalKey { key }))
+    }
+
+    pub fn import_encrypted(
+        &self,
+        blob: String,
+        passphrase: String,
+    ) -> Result<Arc<AskarLocalKey>, ErrorCode> {
+        let jwk = passphrase_decrypt(&blob, KEY_EXPORT_CTY, &passphrase)?;
+        let key = LocalKey::from_jwk_slice(&jwk)?;
+        Ok(Arc::new(AskarLocalKey { key }))
+    }
+
+    pub fn from_did_key(&self, did: String) -> Result<Arc<AskarLocalKey>, ErrorCode> {
+        let key = decode_did_key(&did)?;
+        Ok(Arc::new(AskarLocalKey { key }))
+    }
+
+    pub fn key_agreement_from_did_key(&self, did: String) -> Result<Arc<AskarLocalKey>, ErrorCode> {
+        let key = decode_did_key(&did)?;
+        let key = match key.algorithm() {
+            KeyAlg::X25519 => key,
+            KeyAlg::Ed25519 => key.convert_key(KeyAlg::X25519)?,
+            alg => {
+                return Err(ErrorCode::Unsupported {
+                    message: format!("No X25519 key agreement key for: {}", alg.as_str()),
+                })
+            }
+        };
+        Ok(Arc::new(AskarLocalKey { key }))
+    }
 }
 
 #[uniffi::export]
@@ -185,6 +498,42 @@ impl AskarLocalKey {
         Ok(self.key.to_secret_bytes()?.into_vec())
     }
 
+    pub fn export_encrypted(
+        &self,
+        passphrase: String,
+        kdf_params: AskarKdfParams,
+        enc: AskarKeyAlg,
+    ) -> Result<String, ErrorCode> {
+        let jwk = self.key.to_jwk_secret()?;
+        passphrase_encrypt(&jwk, KEY_EXPORT_CTY, &passphrase, kdf_params, enc.into())
+    }
+
+    pub fn to_multibase_public(&self) -> Result<String, ErrorCode> {
+        encode_multibase_public(&self.key)
+    }
+
+    pub fn to_did_key(&self) -> Result<String, ErrorCode> {
+        Ok(format!(
+            "{}{}",
+            DID_KEY_PREFIX,
+            encode_multibase_public(&self.key)?
+        ))
+    }
+
+    pub fn to_indy_verkey(&self, abbreviated: bool) -> Result<String, ErrorCode> {
+        let bytes = ed25519_public_bytes(&self.key)?;
+        if abbreviated {
+            Ok(format!("~{}", b58_encode(&bytes[16..])))
+        } else {
+            Ok(b58_encode(&bytes))
+        }
+    }
+
+    pub fn to_indy_did(&self) -> Result<String, ErrorCode> {
+        let bytes = ed25519_public_bytes(&self.key)?;
+        Ok(b58_encode(&bytes[..16]))
+    }
+
     pub fn to_key_exchange(
         &self,
         alg: AskarKeyAlg,
@@ -267,6 +616,37 @@ impl AskarLocalKey {
             .to_vec())
     }
 
+    /// Encrypt a file in the framed format of `AskarAeadStream`.
+    pub fn aead_encrypt_file(
+        &self,
+        input_path: String,
+        output_path: String,
+        aad: Option<Vec<u8>>,
+    ) -> Result<(), ErrorCode> {
+        encrypt_file(
+            &self.key,
+            &input_path,
+            &output_path,
+            aad.unwrap_or_default(),
+        )
+    }
+
+    /// Decrypt a file written by `aead_encrypt_file` or an `AskarAeadStream`.
+    /// The output file is only created once the whole file has been authenticated.
+    pub fn aead_decrypt_file(
+        &self,
+        input_path: String,
+        output_path: String,
+        aad: Option<Vec<u8>>,
+    ) -> Result<(), ErrorCode> {
+        decrypt_file(
+            &self.key,
+            &input_path,
+            &output_path,
+            aad.unwrap_or_default(),
+        )
+    }
+
     pub fn sign_message(
         &self,
         message: Vec<u8>,
@@ -0,0 +1,227 @@
+use crate::uffi::error::ErrorCode;
+use aries_askar::{storage::migration::IndySdkToAriesAskarMigration, PassKey, Store};
+use sqlx::{sqlite::SqliteConnectOptions, ConnectOptions, Connection};
+use std::collections::HashMap;
+
+const SQLITE_SCHEME: &str = "sqlite://";
+const WORK_SUFFIX: &str = ".migration";
+// Files kept by SQLite next to a database in WAL mode
+const SIDECAR_SUFFIXES: [&str; 2] = ["-wal", "-shm"];
+
+#[derive(uniffi::Enum, Clone, Copy)]
+pub enum AskarMigrationStage {
+    Counting,
+    Migrating,
+    Verifying,
+}
+
+/// Receives progress updates from an Indy wallet migration.
+/// aries-askar migrates a wallet in a single step, so the migrating stage
+/// is only reported when it starts and when it completes.
+#[uniffi::export(callback_interface)]
+pub trait AskarMigrationProgress: Send + Sync {
+    fn on_progress(&self, stage: AskarMigrationStage, completed: i64, total: i64);
+}
+
+/// The result of an Indy wallet migration. The wallet is verified by comparing the
+/// number of items per category before and after migration. The categories of an
+/// Indy wallet are encrypted, so only the sets of counts can be compared: a migration
+/// which moved items between two categories of the same size would not be detected.
+#[derive(uniffi::Record)]
+pub struct AskarMigrationReport {
+    pub total_items: i64,
+    pub migrated_items: i64,
+    pub categories: HashMap<String, i64>,
+    pub dry_run: bool,
+}
+
+fn migration_error(message: impl Into<String>) -> ErrorCode {
+    ErrorCode::Backend {
+        message: message.into(),
+    }
+}
+
+fn wallet_path(spec_uri: &str) -> Result<&str, ErrorCode> {
+    spec_uri
+        .strip_prefix(SQLITE_SCHEME)
+        .map(|path| path.split('?').next().unwrap_or(path))
+        .filter(|path| !path.is_empty() && *path != ":memory:")
+        .ok_or_else(|| ErrorCode::Unsupported {
+            message: String::from("Only SQLite wallet files can be migrated"),
+        })
+}
+
+fn remove_sidecars(path: &str) {
+    for suffix in SIDECAR_SUFFIXES {
+        std::fs::remove_file(format!("{}{}", path, suffix)).ok();
+    }
+}
+
+// Item types are encrypted deterministically in an Indy wallet, so items can be
+// grouped by category without the wallet key, although the names stay unknown.
+// The working copy is written by SQLite, so that it includes any changes still
+// held in the write-ahead log of the wallet.
+async fn copy_wallet(path: &str, work_path: &str) -> Result<Vec<i64>, ErrorCode> {
+    let mut conn = SqliteConnectOptions::new()
+        .filename(path)
+        .read_only(true)
+        .connect()
+        .await
+        .map_err(|err| migration_error(format!("Error opening Indy wallet: {}", err)))?;
+    let counts = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM items GROUP BY type")
+        .fetch_all(&mut conn)
+        .await
+        .map_err(|err| migration_error(format!("Error reading Indy wallet: {}", err)));
+    let copied = match counts {
+        Ok(_) => sqlx::query("VACUUM INTO ?")
+            .bind(work_path)
+            .execute(&mut conn)
+            .await
+            .map(|_| ())
+            .map_err(|err| migration_error(format!("Error copying Indy wallet: {}", err))),
+        Err(_) => Ok(()),
+    };
+    conn.close().await.ok();
+    copied?;
+    counts
+}
+
+async fn count_store_items(
+    store: &Store,
+    total: i64,
+    progress: &Option<Box<dyn AskarMigrationProgress>>,
+) -> Result<HashMap<String, i64>, ErrorCode> {
+    let mut categories = HashMap::new();
+    let mut completed = 0;
+    let mut scan = store.scan(None, None, None, None, None).await?;
+    while let Some(entries) = scan.fetch_next().await? {
+        completed += entries.len() as i64;
+        for entry in entries {
+            *categories.entry(entry.category).or_insert(0) += 1;
+        }
+        if let Some(progress) = progress {
+            progress.on_progress(AskarMigrationStage::Verifying, completed, total);
+        }
+    }
+    Ok(categories)
+}
+
+fn verify_counts(
+    mut wallet_counts: Vec<i64>,
+    categories: &HashMap<String, i64>,
+) -> Result<(), ErrorCode> {
+    let mut store_counts: Vec<i64> = categories.values().copied().collect();
+    wallet_counts.sort_unstable();
+    store_counts.sort_unstable();
+    if wallet_counts != store_counts {
+        return Err(ErrorCode::Unexpected {
+            message: format!(
+                "Migrated wallet failed verification: expected category counts {:?}, found {:?}",
+                wallet_counts, store_counts
+            ),
+        });
+    }
+    Ok(())
+}
+
+/// Migrate a working copy of the wallet and verify the item counts per category,
+/// before replacing the wallet file. A dry run discards the working copy instead.
+pub(crate) async fn migrate_indy_wallet(
+    spec_uri: &str,
+    wallet_name: &str,
+    wallet_key: &str,
+    kdf_level: &str,
+    dry_run: bool,
+    progress: Option<Box<dyn AskarMigrationProgress>>,
+) -> Result<AskarMigrationReport, ErrorCode> {
+    let path = wallet_path(spec_uri)?;
+    let work_path = format!("{}{}", path, WORK_SUFFIX);
+    let work_uri = format!("{}{}", SQLITE_SCHEME, work_path);
+
+    // Remove any working copy left behind by an interrupted migration
+    Store::remove(&work_uri).await.ok();
+    remove_sidecars(&work_path);
+    let wallet_counts = match copy_wallet(path, &work_path).await {
+        Ok(counts) => counts,
+        Err(err) => {
+            std::fs::remove_file(&work_path).ok();
+            return Err(err);
+        }
+    };
+    let total: i64 = wallet_counts.iter().sum();
+    if let Some(progress) = &progress {
+        progress.on_progress(AskarMigrationStage::Counting, total, total);
+    }
+
+    let result = migrate_working_copy(
+        &work_uri,
+        wallet_name,
+        wallet_key,
+        kdf_level,
+        wallet_counts,
+        &progress,
+    )
+    .await;
+    let categories = match result {
+        Ok(categories) if !dry_run => categories,
+        other => {
+            // Cleanup is best-effort, so that the migration error is reported
+            Store::remove(&work_uri).await.ok();
+            remove_sidecars(&work_path);
+            other?
+        }
+    };
+    if !dry_run {
+        // The working copy is closed, so all of its changes are in the main file
+        if let Err(err) = std::fs::rename(&work_path, path) {
+            Store::remove(&work_uri).await.ok();
+            remove_sidecars(&work_path);
+            return Err(migration_error(format!(
+                "Error replacing Indy wallet: {}",
+                err
+            )));
+        }
+        // The sidecars of the Indy wallet would otherwise be applied to the migrated store
+        remove_sidecars(path);
+    }
+    Ok(AskarMigrationReport {
+        total_items: total,
+        migrated_items: categories.values().sum(),
+        categories,
+        dry_run,
+    })
+}
+
+async fn migrate_working_copy(
+    work_uri: &str,
+    wallet_name: &str,
+    wallet_key: &str,
+    kdf_level: &str,
+    wallet_counts: Vec<i64>,
+    progress: &Option<Box<dyn AskarMigrationProgress>>,
+) -> Result<HashMap<String, i64>, ErrorCode> {
+    let total = wallet_counts.iter().sum();
+    if let Some(progress) = progress {
+        progress.on_progress(AskarMigrationStage::Migrating, 0, total);
+    }
+    // A wrong wallet key is detected here, before the wallet file is touched
+    let migrator =
+        IndySdkToAriesAskarMigration::connect(work_uri, wallet_name, wallet_key, kdf_level).await?;
+    migrator.migrate().await?;
+    if let Some(progress) = progress {
+        progress.on_progress(AskarMigrationStage::Migrating, total, total);
+    }
+
+    let store = Store::open(
+        work_uri,
+        None,
+        PassKey::from(Some(wallet_key)).into_owned(),
+        None,
+    )
+    .await?;
+    let categories = count_store_items(&store, total, progress).await;
+    store.close().await?;
+    let categories = categories?;
+    verify_counts(wallet_counts, &categories)?;
+    Ok(categories)
+}
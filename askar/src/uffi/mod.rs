@@ -1,10 +1,26 @@
+pub mod backup;
 pub mod crypto;
+pub mod didcomm;
+pub mod encoding;
 pub mod entry;
 pub mod error;
+pub mod expiry;
+pub mod filter;
+pub mod jwe;
+pub mod jws;
 pub mod key;
+pub mod migration;
+#[cfg(feature = "postgres")]
+pub mod postgres;
+pub mod reference;
+pub mod reserved;
+pub mod rotation;
 pub mod scan;
 pub mod session;
 pub mod store;
+pub mod store_key;
+pub mod stream;
+pub mod subscription;
 pub mod tags;
 
 #[uniffi::export]
@@ -0,0 +1,70 @@
+use crate::uffi::{
+    error::ErrorCode,
+    key::AskarKeyAlg,
+    reserved::{tag_value, KEY_REFERENCE_TAG},
+};
+use aries_askar::{kms::KeyReference, storage::entry::EntryTag};
+
+#[derive(uniffi::Enum, Clone, Debug, PartialEq)]
+pub enum AskarKeyReference {
+    Local,
+    External { id: String },
+}
+
+impl AskarKeyReference {
+    pub(crate) fn from_tags(tags: &[EntryTag]) -> Self {
+        match tag_value(tags, KEY_REFERENCE_TAG) {
+            Some(id) => AskarKeyReference::External { id: id.to_string() },
+            None => AskarKeyReference::Local,
+        }
+    }
+
+    /// Split into the reference passed to aries-askar and the tag recording it.
+    pub(crate) fn into_parts(self) -> (Option<KeyReference>, Option<EntryTag>) {
+        match self {
+            AskarKeyReference::Local => (None, None),
+            AskarKeyReference::External { id } => (
+                Some(KeyReference::Any(id.clone())),
+                Some(EntryTag::Plaintext(KEY_REFERENCE_TAG.to_string(), id)),
+            ),
+        }
+    }
+}
+
+/// Operations on a key held outside of Askar, such as in a platform keystore.
+/// Keys are identified by the id of their `AskarKeyReference::External` reference.
+#[uniffi::export(callback_interface)]
+pub trait AskarExternalKeyHandler: Send + Sync {
+    fn sign_message(
+        &self,
+        id: String,
+        message: Vec<u8>,
+        sig_type: Option<String>,
+    ) -> Result<Vec<u8>, ErrorCode>;
+
+    fn verify_signature(
+        &self,
+        id: String,
+        message: Vec<u8>,
+        signature: Vec<u8>,
+        sig_type: Option<String>,
+    ) -> Result<bool, ErrorCode>;
+
+    /// Perform a key exchange with the given public key, returning the secret bytes
+    /// of a derived key of type `alg`.
+    fn key_exchange(
+        &self,
+        id: String,
+        alg: AskarKeyAlg,
+        public_jwk: String,
+    ) -> Result<Vec<u8>, ErrorCode>;
+}
+
+pub(crate) fn external_handler<'h>(
+    id: &str,
+    handler: &'h Option<Box<dyn AskarExternalKeyHandler>>,
+) -> Result<&'h dyn AskarExternalKeyHandler, ErrorCode> {
+    handler.as_deref().ok_or_else(|| ErrorCode::Input {
+        message: format!("An external key handler is required for key: {}", id),
+    })
+}
@@ -0,0 +1,86 @@
+use crate::uffi::error::ErrorCode;
+use aries_askar::storage::entry::EntryTag;
+
+// aries-askar does not expose the external reference, expiry time or rotation
+// version of a stored entry, so this wrapper records them in plaintext tags of its
+// own, and keeps the previous versions of rotated keys under key names of its own,
+// both named with the `askar:` prefix. These are part of the stored wallet and are
+// seen by other Askar clients sharing it, but the tags are hidden from the tags
+// returned by this wrapper. Key names and plaintext tag names with the prefix are
+// reserved for this use.
+const RESERVED_PREFIX: &str = "askar:";
+
+/// The prefix of the names under which previous versions of rotated keys are kept.
+pub(crate) const KEY_HISTORY_PREFIX: &str = "askar:history:";
+
+/// The id of an externally held key.
+pub(crate) const KEY_REFERENCE_TAG: &str = "askar:reference";
+
+/// The absolute expiry time, in milliseconds since the Unix epoch. Values are
+/// zero-padded so that tag filters can compare them as strings.
+pub(crate) const EXPIRY_TAG: &str = "askar:expiry";
+
+/// The version of a key, which is only recorded once the key has been rotated.
+pub(crate) const KEY_VERSION_TAG: &str = "askar:version";
+
+/// Tags recorded by this wrapper, which are hidden from the entry tags.
+pub(crate) fn is_reserved_tag(tag: &EntryTag) -> bool {
+    matches!(tag, EntryTag::Plaintext(name, _) if name.starts_with(RESERVED_PREFIX))
+}
+
+/// Reject key names which may collide with the keys recorded by this wrapper.
+pub(crate) fn check_key_name(name: &str) -> Result<(), ErrorCode> {
+    if name.starts_with(RESERVED_PREFIX) {
+        return Err(ErrorCode::input(format!(
+            "Key names starting with {} are reserved: {}",
+            RESERVED_PREFIX, name
+        )));
+    }
+    Ok(())
+}
+
+/// Reject plaintext tag names which may collide with the tags recorded by this wrapper.
+pub(crate) fn check_tag_names(tags: &[EntryTag]) -> Result<(), ErrorCode> {
+    match tags.iter().find(|tag| is_reserved_tag(tag)) {
+        Some(tag) => Err(ErrorCode::input(format!(
+            "Plaintext tag names starting with {} are reserved: {}",
+            RESERVED_PREFIX,
+            tag.name()
+        ))),
+        None => Ok(()),
+    }
+}
+
+pub(crate) fn is_tag(tag: &EntryTag, tag_name: &str) -> bool {
+    matches!(tag, EntryTag::Plaintext(name, _) if name == tag_name)
+}
+
+/// The value of a reserved tag, if present.
+pub(crate) fn tag_value<'t>(tags: &'t [EntryTag], tag_name: &str) -> Option<&'t str> {
+    tags.iter().find_map(|tag| match tag {
+        EntryTag::Plaintext(name, value) if name == tag_name => Some(value.as_str()),
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reserved_tag_names() {
+        let tags = vec![
+            EntryTag::Plaintext("state".to_owned(), "done".to_owned()),
+            EntryTag::Encrypted("askar:expiry".to_owned(), "0".to_owned()),
+        ];
+        assert!(check_tag_names(&tags).is_ok());
+        let tags = vec![
+            EntryTag::Plaintext("state".to_owned(), "done".to_owned()),
+            EntryTag::Plaintext(EXPIRY_TAG.to_owned(), "0".to_owned()),
+        ];
+        assert!(matches!(
+            check_tag_names(&tags),
+            Err(ErrorCode::Input { .. })
+        ));
+    }
+}
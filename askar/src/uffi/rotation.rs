@@ -0,0 +1,33 @@
+use crate::uffi::{
+    expiry::{format_timestamp, now_ms},
+    reserved::{is_tag, tag_value, KEY_HISTORY_PREFIX, KEY_VERSION_TAG},
+};
+use aries_askar::storage::entry::EntryTag;
+
+// aries-askar cannot rename a key entry, so the previous key of a rotation is
+// copied to a versioned name. Keys which were never rotated are version 1.
+pub(crate) const ROTATED_AT_TAG: &str = "rotated_at";
+
+pub(crate) fn is_version_tag(tag: &EntryTag) -> bool {
+    is_tag(tag, KEY_VERSION_TAG)
+}
+
+pub(crate) fn version_from_tags(tags: &[EntryTag]) -> i64 {
+    tag_value(tags, KEY_VERSION_TAG)
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(1)
+}
+
+pub(crate) fn version_tag(version: i64) -> EntryTag {
+    EntryTag::Plaintext(KEY_VERSION_TAG.to_string(), version.to_string())
+}
+
+/// Record the time of a rotation, in zero-padded milliseconds since the Unix epoch.
+pub(crate) fn rotated_at_tag() -> EntryTag {
+    EntryTag::Plaintext(ROTATED_AT_TAG.to_string(), format_timestamp(now_ms()))
+}
+
+/// The name under which a previous version of a rotated key is kept.
+pub(crate) fn versioned_key_name(name: &str, version: i64) -> String {
+    format!("{}{}@v{}", KEY_HISTORY_PREFIX, name, version)
+}
@@ -1,16 +1,73 @@
 use crate::uffi::{entry::AskarEntry, error::ErrorCode};
 use aries_askar::storage::entry::{Entry, Scan};
-use std::sync::Arc;
+use std::{collections::VecDeque, sync::Arc};
 use tokio::sync::Mutex;
 
+const DEFAULT_PAGE_SIZE: usize = 32;
+
+macro_rules! SCAN_CLOSED_ERROR {
+    () => {
+        ErrorCode::Unexpected {
+            message: String::from("Scan is already closed"),
+        }
+    };
+}
+
+struct ScanState {
+    scan: Option<Scan<'static, Entry>>,
+    pending: VecDeque<Entry>,
+}
+
+impl ScanState {
+    async fn next_batch(&mut self, page_size: Option<usize>) -> Result<Vec<Entry>, ErrorCode> {
+        if page_size.is_none() && self.pending.is_empty() {
+            if let Some(scan) = self.scan.as_mut() {
+                let entries = scan.fetch_next().await?;
+                if entries.is_none() {
+                    self.scan = None;
+                }
+                return Ok(entries.unwrap_or_default());
+            }
+        }
+        let page_size = page_size.unwrap_or(DEFAULT_PAGE_SIZE);
+        while self.pending.len() < page_size {
+            let Some(scan) = self.scan.as_mut() else {
+                break;
+            };
+            match scan.fetch_next().await? {
+                Some(entries) => self.pending.extend(entries),
+                None => self.scan = None,
+            }
+        }
+        let count = page_size.min(self.pending.len());
+        Ok(self.pending.drain(..count).collect())
+    }
+
+    async fn fetch_remaining(&mut self) -> Result<Vec<Entry>, ErrorCode> {
+        let mut entries: Vec<Entry> = self.pending.drain(..).collect();
+        if let Some(scan) = self.scan.as_mut() {
+            while let Some(mut batch) = scan.fetch_next().await? {
+                entries.append(&mut batch);
+            }
+            self.scan = None;
+        }
+        Ok(entries)
+    }
+}
+
 pub struct AskarScan {
-    scan: Mutex<Scan<'static, Entry>>,
+    state: Mutex<Option<ScanState>>, // Option is used to allow for the scan to be closed
+    page_size: Option<usize>,
 }
 
 impl AskarScan {
-    pub fn new(scan: Scan<'static, Entry>) -> Self {
+    pub fn new(scan: Scan<'static, Entry>, page_size: Option<usize>) -> Self {
         Self {
-            scan: Mutex::new(scan),
+            state: Mutex::new(Some(ScanState {
+                scan: Some(scan),
+                pending: VecDeque::new(),
+            })),
+            page_size,
         }
     }
 }
@@ -18,10 +75,13 @@ impl AskarScan {
 #[uniffi::export(async_runtime = "tokio")]
 impl AskarScan {
     pub async fn next(&self) -> Result<Option<Vec<Arc<AskarEntry>>>, ErrorCode> {
-        let mut scan = self.scan.lock().await;
-        let entries = scan.fetch_next().await?;
+        let mut state = self.state.lock().await;
+        let entries = state
+            .as_mut()
+            .ok_or(SCAN_CLOSED_ERROR!())?
+            .next_batch(self.page_size)
+            .await?;
         let entries: Vec<Arc<AskarEntry>> = entries
-            .unwrap_or(vec![])
             .into_iter()
             .map(|entry| Arc::new(AskarEntry::new(entry)))
             .collect();
@@ -33,15 +93,21 @@ impl AskarScan {
     }
 
     pub async fn fetch_all(&self) -> Result<Vec<Arc<AskarEntry>>, ErrorCode> {
-        let mut scan = self.scan.lock().await;
-        let mut entries = vec![];
-        while let Some(mut batch) = scan.fetch_next().await? {
-            entries.append(&mut batch);
-        }
+        let mut state = self.state.lock().await;
+        let entries = state
+            .as_mut()
+            .ok_or(SCAN_CLOSED_ERROR!())?
+            .fetch_remaining()
+            .await?;
         let entries = entries
             .into_iter()
             .map(|entry| Arc::new(AskarEntry::new(entry)))
             .collect();
         Ok(entries)
     }
+
+    pub async fn close(&self) -> Result<(), ErrorCode> {
+        self.state.lock().await.take();
+        Ok(())
+    }
 }
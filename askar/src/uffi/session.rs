@@ -1,15 +1,26 @@
 use crate::uffi::{
-    entry::AskarEntry, entry::AskarKeyEntry, error::ErrorCode, key::AskarLocalKey,
-    tags::EntryTagSet,
+    entry::{
+        into_entry_tags, merge_json_patch, parse_json, AskarEntry, AskarEntryTag, AskarKeyEntry,
+    },
+    error::ErrorCode,
+    expiry::{remaining_expiry_ms, with_expiry_tag},
+    filter::AskarTagFilter,
+    key::AskarLocalKey,
+    reference::AskarKeyReference,
+    reserved::{check_key_name, is_reserved_tag},
+    rotation::{
+        is_version_tag, rotated_at_tag, version_from_tags, version_tag, versioned_key_name,
+    },
+    subscription::{EntryChange, Subscriptions},
 };
 use aries_askar::{
-    Session,
-    storage::entry::{EntryOperation, TagFilter},
+    storage::entry::{EntryOperation, EntryTag},
+    ErrorKind, Session,
 };
-use std::{str::FromStr, sync::Arc};
+use std::sync::Arc;
 use tokio::sync::Mutex;
 
-#[derive(uniffi::Enum)]
+#[derive(uniffi::Enum, Clone, Copy)]
 pub enum AskarEntryOperation {
     Insert,
     Replace,
@@ -26,6 +37,23 @@ impl Into<EntryOperation> for AskarEntryOperation {
     }
 }
 
+#[derive(uniffi::Record)]
+pub struct AskarEntryUpdate {
+    pub operation: AskarEntryOperation,
+    pub category: String,
+    pub name: String,
+    pub value: Vec<u8>,
+    pub tags: Option<Vec<AskarEntryTag>>,
+    pub expiry_ms: Option<i64>,
+}
+
+#[derive(uniffi::Record)]
+pub struct AskarEntryUpdateResult {
+    pub category: String,
+    pub name: String,
+    pub error: Option<ErrorCode>,
+}
+
 macro_rules! SESSION_CLOSED_ERROR {
     () => {
         ErrorCode::Unexpected {
@@ -34,29 +62,118 @@ macro_rules! SESSION_CLOSED_ERROR {
     };
 }
 
+macro_rules! NOT_TRANSACTION_ERROR {
+    () => {
+        ErrorCode::Input {
+            message: String::from("Session is not a transaction"),
+        }
+    };
+}
+
 pub struct AskarSession {
     session: Mutex<Option<Session>>,
+    is_transaction: bool,
+    profile: String,
+    subscriptions: Arc<Subscriptions>,
+    pending: Mutex<Vec<EntryChange>>, // Changes held until a transaction is committed
 }
 
 impl AskarSession {
-    pub fn new(session: Session) -> Self {
+    pub(crate) fn new(
+        session: Session,
+        is_transaction: bool,
+        profile: String,
+        subscriptions: Arc<Subscriptions>,
+    ) -> Self {
         Self {
             session: Mutex::new(Some(session)),
+            is_transaction,
+            profile,
+            subscriptions,
+            pending: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Describe a change for subscribers, or return None when nobody is watching.
+    /// The tags of an entry are fetched before it is removed, to match tag filters.
+    async fn entry_change(
+        &self,
+        session: &mut Session,
+        operation: AskarEntryOperation,
+        category: &str,
+        name: &str,
+        tags: Option<&[EntryTag]>,
+    ) -> Result<Option<EntryChange>, ErrorCode> {
+        if !self.subscriptions.is_watching(&self.profile, category) {
+            return Ok(None);
         }
+        let tags = match operation {
+            AskarEntryOperation::Remove => session
+                .fetch(category, name, false)
+                .await?
+                .map(|entry| entry.tags)
+                .unwrap_or_default(),
+            _ => tags.map(<[EntryTag]>::to_vec).unwrap_or_default(),
+        };
+        Ok(Some(EntryChange {
+            operation,
+            category: category.to_string(),
+            name: Some(name.to_string()),
+            tags,
+        }))
+    }
+
+    // Must not be called with the session locked, as listeners may use the session
+    async fn notify(&self, changes: Vec<EntryChange>) {
+        if self.is_transaction {
+            self.pending.lock().await.extend(changes);
+        } else {
+            self.subscriptions.notify(&self.profile, changes);
+        }
+    }
+}
+
+#[uniffi::export]
+impl AskarSession {
+    pub fn is_transaction(&self) -> bool {
+        self.is_transaction
     }
 }
 
 #[uniffi::export(async_runtime = "tokio")]
 impl AskarSession {
     pub async fn close(&self) -> Result<(), ErrorCode> {
+        // Dropping an uncommitted transaction rolls it back
         self.session.lock().await.take();
+        self.pending.lock().await.clear();
+        Ok(())
+    }
+
+    pub async fn commit(&self) -> Result<(), ErrorCode> {
+        if !self.is_transaction {
+            return Err(NOT_TRANSACTION_ERROR!());
+        }
+        let session = self.session.lock().await.take();
+        session.ok_or(SESSION_CLOSED_ERROR!())?.commit().await?;
+        let changes = std::mem::take(&mut *self.pending.lock().await);
+        self.subscriptions.notify(&self.profile, changes);
+        Ok(())
+    }
+
+    pub async fn rollback(&self) -> Result<(), ErrorCode> {
+        if !self.is_transaction {
+            return Err(NOT_TRANSACTION_ERROR!());
+        }
+        let session = self.session.lock().await.take();
+        self.pending.lock().await.clear();
+        session.ok_or(SESSION_CLOSED_ERROR!())?.rollback().await?;
         Ok(())
     }
 
     pub async fn count(
         &self,
         category: String,
-        tag_filter: Option<String>,
+        tag_filter: Option<Arc<AskarTagFilter>>,
     ) -> Result<i64, ErrorCode> {
         Ok(self
             .session
@@ -66,7 +183,7 @@ impl AskarSession {
             .ok_or(SESSION_CLOSED_ERROR!())?
             .count(
                 Some(&category),
-                tag_filter.as_deref().map(TagFilter::from_str).transpose()?,
+                tag_filter.map(|f| f.filter.clone()),
             )
             .await?)
     }
@@ -91,7 +208,7 @@ impl AskarSession {
     pub async fn fetch_all(
         &self,
         category: String,
-        tag_filter: Option<String>,
+        tag_filter: Option<Arc<AskarTagFilter>>,
         limit: Option<i64>,
         for_update: bool,
     ) -> Result<Vec<Arc<AskarEntry>>, ErrorCode> {
@@ -103,7 +220,7 @@ impl AskarSession {
             .ok_or(SESSION_CLOSED_ERROR!())?
             .fetch_all(
                 Some(&category),
-                tag_filter.as_deref().map(TagFilter::from_str).transpose()?,
+                tag_filter.map(|f| f.filter.clone()),
                 limit,
                 for_update,
             )
@@ -120,25 +237,16 @@ impl AskarSession {
         category: String,
         name: String,
         value: Vec<u8>,
-        tags: Option<String>,
+        tags: Option<Vec<AskarEntryTag>>,
         expiry_ms: Option<i64>,
     ) -> Result<(), ErrorCode> {
-        let tags = if let Some(tags) = tags {
-            Some(
-                serde_json::from_str::<EntryTagSet<'static>>(&tags)
-                    .map_err(|err| ErrorCode::Input {
-                        message: format!("Error decoding tags: {}", err),
-                    })?
-                    .into_vec(),
-            )
-        } else {
-            None
-        };
-        self.session
-            .lock()
-            .await
-            .as_mut()
-            .ok_or(SESSION_CLOSED_ERROR!())?
+        let tags = with_expiry_tag(into_entry_tags(tags)?, expiry_ms);
+        let mut guard = self.session.lock().await;
+        let session = guard.as_mut().ok_or(SESSION_CLOSED_ERROR!())?;
+        let change = self
+            .entry_change(session, operation, &category, &name, tags.as_deref())
+            .await?;
+        session
             .update(
                 operation.into(),
                 &category,
@@ -148,25 +256,175 @@ impl AskarSession {
                 expiry_ms,
             )
             .await?;
+        drop(guard);
+        self.notify(change.into_iter().collect()).await;
         Ok(())
     }
 
+    pub async fn update_batch(
+        &self,
+        updates: Vec<AskarEntryUpdate>,
+    ) -> Result<Vec<AskarEntryUpdateResult>, ErrorCode> {
+        let mut guard = self.session.lock().await;
+        let session = guard.as_mut().ok_or(SESSION_CLOSED_ERROR!())?;
+        let mut results = Vec::with_capacity(updates.len());
+        let mut changes = Vec::new();
+        for update in updates {
+            let tags = match into_entry_tags(update.tags) {
+                Ok(tags) => with_expiry_tag(tags, update.expiry_ms),
+                Err(err) => {
+                    results.push(AskarEntryUpdateResult {
+                        category: update.category,
+                        name: update.name,
+                        error: Some(err),
+                    });
+                    continue;
+                }
+            };
+            let result = match self
+                .entry_change(
+                    session,
+                    update.operation,
+                    &update.category,
+                    &update.name,
+                    tags.as_deref(),
+                )
+                .await
+            {
+                Ok(change) => session
+                    .update(
+                        update.operation.into(),
+                        &update.category,
+                        &update.name,
+                        Some(&update.value),
+                        tags.as_deref(),
+                        update.expiry_ms,
+                    )
+                    .await
+                    .map(|_| changes.extend(change))
+                    .map_err(ErrorCode::from),
+                Err(err) => Err(err),
+            };
+            results.push(AskarEntryUpdateResult {
+                category: update.category,
+                name: update.name,
+                error: result.err(),
+            });
+        }
+        drop(guard);
+        self.notify(changes).await;
+        Ok(results)
+    }
+
+    pub async fn insert_json(
+        &self,
+        category: String,
+        name: String,
+        json: String,
+        tags: Option<Vec<AskarEntryTag>>,
+        expiry_ms: Option<i64>,
+    ) -> Result<(), ErrorCode> {
+        parse_json(json.as_bytes())?;
+        self.update(
+            AskarEntryOperation::Insert,
+            category,
+            name,
+            json.into_bytes(),
+            tags,
+            expiry_ms,
+        )
+        .await
+    }
+
+    pub async fn replace_json(
+        &self,
+        category: String,
+        name: String,
+        json: String,
+        tags: Option<Vec<AskarEntryTag>>,
+        expiry_ms: Option<i64>,
+    ) -> Result<(), ErrorCode> {
+        parse_json(json.as_bytes())?;
+        self.update(
+            AskarEntryOperation::Replace,
+            category,
+            name,
+            json.into_bytes(),
+            tags,
+            expiry_ms,
+        )
+        .await
+    }
+
+    /// Apply a JSON merge patch (RFC 7396) to the value of an entry, keeping its tags.
+    /// The entry is fetched for update, so the patch is atomic within a transaction.
+    /// Returns the patched value.
+    pub async fn patch_json(
+        &self,
+        category: String,
+        name: String,
+        json_merge_patch: String,
+    ) -> Result<String, ErrorCode> {
+        let patch = parse_json(json_merge_patch.as_bytes())?;
+        let mut guard = self.session.lock().await;
+        let session = guard.as_mut().ok_or(SESSION_CLOSED_ERROR!())?;
+        let entry = session
+            .fetch(&category, &name, true)
+            .await?
+            .ok_or_else(|| ErrorCode::NotFound {
+                message: format!("Entry not found: {}/{}", category, name),
+            })?;
+        let mut value = parse_json(&entry.value)?;
+        merge_json_patch(&mut value, patch);
+        let value = value.to_string();
+        let change = self
+            .entry_change(
+                session,
+                AskarEntryOperation::Replace,
+                &category,
+                &name,
+                Some(&entry.tags),
+            )
+            .await?;
+        session
+            .update(
+                EntryOperation::Replace,
+                &category,
+                &name,
+                Some(value.as_bytes()),
+                Some(&entry.tags),
+                remaining_expiry_ms(&entry.tags),
+            )
+            .await?;
+        drop(guard);
+        self.notify(change.into_iter().collect()).await;
+        Ok(value)
+    }
+
     pub async fn remove_all(
         &self,
         category: String,
-        tag_filter: Option<String>,
+        tag_filter: Option<Arc<AskarTagFilter>>,
     ) -> Result<i64, ErrorCode> {
-        Ok(self
+        let removed = self
             .session
             .lock()
             .await
             .as_mut()
             .ok_or(SESSION_CLOSED_ERROR!())?
-            .remove_all(
-                Some(&category),
-                tag_filter.as_deref().map(TagFilter::from_str).transpose()?,
-            )
-            .await?)
+            .remove_all(Some(&category), tag_filter.map(|f| f.filter.clone()))
+            .await?;
+        if removed > 0 {
+            // The removed entries are not known, so a single event is reported for them
+            self.notify(vec![EntryChange {
+                operation: AskarEntryOperation::Remove,
+                category,
+                name: None,
+                tags: Vec::new(),
+            }])
+            .await;
+        }
+        Ok(removed)
     }
 
     pub async fn insert_key(
@@ -174,20 +432,17 @@ impl AskarSession {
         name: String,
         key: Arc<AskarLocalKey>,
         metadata: Option<String>,
-        tags: Option<String>,
+        reference: Option<AskarKeyReference>,
+        tags: Option<Vec<AskarEntryTag>>,
         expiry_ms: Option<i64>,
     ) -> Result<(), ErrorCode> {
-        let tags = if let Some(tags) = tags {
-            Some(
-                serde_json::from_str::<EntryTagSet<'static>>(&tags)
-                    .map_err(|err| ErrorCode::Input {
-                        message: format!("Error decoding tags: {}", err),
-                    })?
-                    .into_vec(),
-            )
-        } else {
-            None
-        };
+        check_key_name(&name)?;
+        let (reference, reference_tag) = reference.unwrap_or(AskarKeyReference::Local).into_parts();
+        let mut tags = into_entry_tags(tags)?;
+        if let Some(tag) = reference_tag {
+            tags.get_or_insert_with(Vec::new).push(tag);
+        }
+        let tags = with_expiry_tag(tags, expiry_ms);
         self.session
             .lock()
             .await
@@ -197,7 +452,7 @@ impl AskarSession {
                 &name,
                 &key.key,
                 metadata.as_deref(),
-                None,
+                reference,
                 tags.as_deref(),
                 expiry_ms,
             )
@@ -225,11 +480,11 @@ impl AskarSession {
         &self,
         algorithm: Option<String>,
         thumbprint: Option<String>,
-        tag_filter: Option<String>,
+        tag_filter: Option<Arc<AskarTagFilter>>,
         limit: Option<i64>,
         for_update: bool,
     ) -> Result<Vec<Arc<AskarKeyEntry>>, ErrorCode> {
-        let tag_filter = tag_filter.as_deref().map(TagFilter::from_str).transpose()?;
+        let tag_filter = tag_filter.map(|f| f.filter.clone());
         let keys = self
             .session
             .lock()
@@ -250,40 +505,134 @@ impl AskarSession {
             .collect())
     }
 
+    /// Remove a key together with the previous versions kept by `rotate_key`. A rotated
+    /// key can only be removed within a transaction, so that no versions are left behind.
     pub async fn remove_key(&self, name: String) -> Result<(), ErrorCode> {
-        self.session
-            .lock()
-            .await
-            .as_mut()
-            .ok_or(SESSION_CLOSED_ERROR!())?
-            .remove_key(&name)
-            .await?;
+        let mut guard = self.session.lock().await;
+        let session = guard.as_mut().ok_or(SESSION_CLOSED_ERROR!())?;
+        let version = session
+            .fetch_key(&name, self.is_transaction)
+            .await?
+            .map(|entry| version_from_tags(entry.tags_as_slice()))
+            .unwrap_or(1);
+        if version > 1 && !self.is_transaction {
+            return Err(NOT_TRANSACTION_ERROR!());
+        }
+        for previous in 1..version {
+            match session
+                .remove_key(&versioned_key_name(&name, previous))
+                .await
+            {
+                Err(err) if err.kind() != ErrorKind::NotFound => return Err(err.into()),
+                _ => (),
+            }
+        }
+        session.remove_key(&name).await?;
         Ok(())
     }
 
+    /// Replace the key stored under a name, keeping the previous key under a reserved
+    /// versioned name with a `rotated_at` tag. The new key keeps the metadata and tags
+    /// of the previous key. Keys can only be rotated within a transaction, so that the
+    /// rotation is atomic. Returns the version of the new key.
+    pub async fn rotate_key(
+        &self,
+        name: String,
+        new_key: Arc<AskarLocalKey>,
+    ) -> Result<i64, ErrorCode> {
+        if !self.is_transaction {
+            return Err(NOT_TRANSACTION_ERROR!());
+        }
+        check_key_name(&name)?;
+        let mut guard = self.session.lock().await;
+        let session = guard.as_mut().ok_or(SESSION_CLOSED_ERROR!())?;
+        let current = session
+            .fetch_key(&name, true)
+            .await?
+            .ok_or_else(|| ErrorCode::NotFound {
+                message: format!("Key not found: {}", name),
+            })?;
+        let version = version_from_tags(current.tags_as_slice());
+        let (reference, _) = AskarKeyReference::from_tags(current.tags_as_slice()).into_parts();
+        let mut previous_tags = current.tags_as_slice().to_vec();
+        previous_tags.retain(|tag| !is_version_tag(tag));
+        previous_tags.extend([version_tag(version), rotated_at_tag()]);
+        // The previous key is copied first, so a conflicting version leaves the key in place
+        session
+            .insert_key(
+                &versioned_key_name(&name, version),
+                &current.load_local_key()?,
+                current.metadata(),
+                reference,
+                Some(&previous_tags),
+                remaining_expiry_ms(current.tags_as_slice()),
+            )
+            .await?;
+        session.remove_key(&name).await?;
+        let mut tags: Vec<EntryTag> = current
+            .tags_as_slice()
+            .iter()
+            .filter(|tag| !is_reserved_tag(tag))
+            .cloned()
+            .collect();
+        tags.push(version_tag(version + 1));
+        session
+            .insert_key(
+                &name,
+                &new_key.key,
+                current.metadata(),
+                None,
+                Some(&tags),
+                None,
+            )
+            .await?;
+        Ok(version + 1)
+    }
+
+    /// Fetch the current key stored under a name or one of its previous versions.
+    pub async fn fetch_key_version(
+        &self,
+        name: String,
+        version: i64,
+    ) -> Result<Option<Arc<AskarKeyEntry>>, ErrorCode> {
+        let mut guard = self.session.lock().await;
+        let session = guard.as_mut().ok_or(SESSION_CLOSED_ERROR!())?;
+        let key = match session.fetch_key(&name, false).await? {
+            Some(entry) if version_from_tags(entry.tags_as_slice()) == version => Some(entry),
+            _ => {
+                session
+                    .fetch_key(&versioned_key_name(&name, version), false)
+                    .await?
+            }
+        };
+        Ok(key.map(|entry| Arc::new(AskarKeyEntry::new(entry))))
+    }
+
+    /// Replace the metadata, tags and expiry of a key. As in aries-askar, tags or an expiry
+    /// which are not given are cleared; the reference of an external key and its rotation
+    /// version are kept.
     pub async fn update_key(
         &self,
         name: String,
         metadata: Option<String>,
-        tags: Option<String>,
+        tags: Option<Vec<AskarEntryTag>>,
         expiry_ms: Option<i64>,
     ) -> Result<(), ErrorCode> {
-        let tags = if let Some(tags) = tags {
-            Some(
-                serde_json::from_str::<EntryTagSet<'static>>(&tags)
-                    .map_err(|err| ErrorCode::Input {
-                        message: format!("Error decoding tags: {}", err),
-                    })?
-                    .into_vec(),
-            )
-        } else {
-            None
-        };
-        self.session
-            .lock()
-            .await
-            .as_mut()
-            .ok_or(SESSION_CLOSED_ERROR!())?
+        let tags = into_entry_tags(tags)?;
+        let mut guard = self.session.lock().await;
+        let session = guard.as_mut().ok_or(SESSION_CLOSED_ERROR!())?;
+        let existing = session.fetch_key(&name, false).await?;
+        let existing_tags = existing
+            .as_ref()
+            .map(|entry| entry.tags_as_slice())
+            .unwrap_or_default();
+        let mut tags = tags.unwrap_or_default();
+        // Replacing the tags must keep the reference of an external key and its version
+        let (_, reference_tag) = AskarKeyReference::from_tags(existing_tags).into_parts();
+        tags.extend(reference_tag);
+        tags.extend(existing_tags.iter().filter(|t| is_version_tag(t)).cloned());
+        let tags = with_expiry_tag(Some(tags), expiry_ms);
+        session
             .update_key(&name, metadata.as_deref(), tags.as_deref(), expiry_ms)
             .await?;
         Ok(())
@@ -1,11 +1,28 @@
-use crate::uffi::{error::ErrorCode, scan::AskarScan, session::AskarSession};
+use crate::uffi::{
+    backup::{restore_backup, write_backup},
+    error::ErrorCode,
+    expiry::{expires_within_filter, remaining_expiry_ms},
+    filter::AskarTagFilter,
+    migration::{migrate_indy_wallet, AskarMigrationProgress, AskarMigrationReport},
+    reference::AskarKeyReference,
+    scan::AskarScan,
+    session::AskarSession,
+    store_key::{
+        derive_store_key, resolve_key_method, resolve_open_key_method, AskarDerivedStoreKey,
+        AskarPassKey, AskarStoreKeyMethod,
+    },
+    subscription::{AskarEntryListener, AskarSubscription, Subscriptions},
+};
 use aries_askar::{
-    PassKey, Store, StoreKeyMethod,
-    storage::{entry::TagFilter, generate_raw_store_key, migration::IndySdkToAriesAskarMigration},
+    Store,
+    storage::{entry::EntryOperation, generate_raw_store_key},
 };
-use std::{str::FromStr, sync::Arc};
+use std::sync::Arc;
 use tokio::sync::RwLock;
 
+#[cfg(feature = "postgres")]
+use crate::uffi::postgres::AskarPostgresOptions;
+
 macro_rules! STORE_CLOSED_ERROR {
     () => {
         ErrorCode::Unexpected {
@@ -14,6 +31,110 @@ macro_rules! STORE_CLOSED_ERROR {
     };
 }
 
+#[derive(uniffi::Record)]
+pub struct AskarStoreCopyStats {
+    pub profiles: i64,
+    pub entries: i64,
+    pub keys: i64,
+}
+
+async fn collect_copy_stats(store: &Store) -> Result<AskarStoreCopyStats, ErrorCode> {
+    let profiles = store.list_profiles().await?;
+    let mut stats = AskarStoreCopyStats {
+        profiles: profiles.len() as i64,
+        entries: 0,
+        keys: 0,
+    };
+    for profile in profiles {
+        let mut session = store.session(Some(profile)).await?;
+        stats.entries += session.count(None, None).await?;
+        stats.keys += session
+            .fetch_all_keys(None, None, None, None, false)
+            .await?
+            .len() as i64;
+    }
+    Ok(stats)
+}
+
+async fn copy_profile_contents(
+    store: &Store,
+    from_profile: String,
+    to_profile: String,
+) -> Result<AskarStoreCopyStats, ErrorCode> {
+    store.create_profile(Some(to_profile.clone())).await?;
+    let stats = copy_into_profile(store, from_profile, to_profile.clone()).await;
+    if stats.is_err() {
+        // Remove the partial copy, so that the copy can be retried
+        store.remove_profile(to_profile).await.ok();
+    }
+    stats
+}
+
+async fn copy_into_profile(
+    store: &Store,
+    from_profile: String,
+    to_profile: String,
+) -> Result<AskarStoreCopyStats, ErrorCode> {
+    let mut stats = AskarStoreCopyStats {
+        profiles: 1,
+        entries: 0,
+        keys: 0,
+    };
+    let mut target = store.transaction(Some(to_profile)).await?;
+    let mut scan = store
+        .scan(Some(from_profile.clone()), None, None, None, None)
+        .await?;
+    while let Some(entries) = scan.fetch_next().await? {
+        for entry in entries {
+            target
+                .update(
+                    EntryOperation::Insert,
+                    &entry.category,
+                    &entry.name,
+                    Some(&entry.value),
+                    Some(&entry.tags),
+                    remaining_expiry_ms(&entry.tags),
+                )
+                .await?;
+            stats.entries += 1;
+        }
+    }
+    let keys = store
+        .session(Some(from_profile))
+        .await?
+        .fetch_all_keys(None, None, None, None, false)
+        .await?;
+    for key in keys {
+        let (reference, _) = AskarKeyReference::from_tags(key.tags_as_slice()).into_parts();
+        target
+            .insert_key(
+                key.name(),
+                &key.load_local_key()?,
+                key.metadata(),
+                reference,
+                Some(key.tags_as_slice()),
+                remaining_expiry_ms(key.tags_as_slice()),
+            )
+            .await?;
+        stats.keys += 1;
+    }
+    target.commit().await?;
+    Ok(stats)
+}
+
+async fn replace_profile(
+    store: &Store,
+    from_profile: String,
+    to_profile: String,
+    is_default: bool,
+) -> Result<(), ErrorCode> {
+    if is_default {
+        store.set_default_profile(to_profile).await?;
+    }
+    store.remove_profile(from_profile).await?;
+    Ok(())
+}
+
 pub struct AskarStoreManager {}
 
 impl AskarStoreManager {
@@ -24,9 +145,31 @@ impl AskarStoreManager {
 
 #[uniffi::export]
 impl AskarStoreManager {
-    pub fn generate_raw_store_key(&self, seed: Option<String>) -> Result<String, ErrorCode> {
+    pub fn generate_raw_store_key(&self, seed: Option<String>) -> Result<AskarPassKey, ErrorCode> {
         let key = generate_raw_store_key(seed.as_ref().map(|s| s.as_bytes()))?;
-        Ok(key.to_string())
+        Ok(AskarPassKey::RawKey {
+            key: key.to_string(),
+        })
+    }
+
+    /// Derive a raw store key from a passphrase, separately from opening a store.
+    pub fn derive_store_key(
+        &self,
+        key_method: AskarStoreKeyMethod,
+        passphrase: String,
+        salt: Option<Vec<u8>>,
+    ) -> Result<AskarDerivedStoreKey, ErrorCode> {
+        derive_store_key(key_method, &passphrase, salt)
+    }
+}
+
+#[cfg(feature = "postgres")]
+#[uniffi::export]
+impl AskarStoreManager {
+    /// Build a Postgres store URI. Only available with the `postgres` feature,
+    /// as the URI can only be provisioned or opened with it.
+    pub fn postgres_uri(&self, options: AskarPostgresOptions) -> String {
+        options.to_uri()
     }
 }
 
@@ -35,39 +178,29 @@ impl AskarStoreManager {
     pub async fn provision(
         &self,
         spec_uri: String,
-        key_method: Option<String>,
-        pass_key: Option<String>,
+        key_method: Option<AskarStoreKeyMethod>,
+        pass_key: AskarPassKey,
         profile: Option<String>,
         recreate: bool,
     ) -> Result<Arc<AskarStore>, ErrorCode> {
-        let key_method = match key_method {
-            Some(method) => StoreKeyMethod::parse_uri(&method)?,
-            None => StoreKeyMethod::default(),
-        };
-        let pass_key = PassKey::from(pass_key.as_deref()).into_owned();
+        let key_method = resolve_key_method(key_method, &pass_key)?;
+        let pass_key = pass_key.to_pass_key();
         let store =
             Store::provision(spec_uri.as_str(), key_method, pass_key, profile, recreate).await?;
-        Ok(Arc::new(AskarStore {
-            store: RwLock::new(Some(store)),
-        }))
+        Ok(Arc::new(AskarStore::new(store)))
     }
 
     pub async fn open(
         &self,
         spec_uri: String,
-        key_method: Option<String>,
-        pass_key: Option<String>,
+        key_method: Option<AskarStoreKeyMethod>,
+        pass_key: AskarPassKey,
         profile: Option<String>,
     ) -> Result<Arc<AskarStore>, ErrorCode> {
-        let key_method = match key_method {
-            Some(method) => Some(StoreKeyMethod::parse_uri(&method)?),
-            None => None,
-        };
-        let pass_key = PassKey::from(pass_key.as_deref()).into_owned();
+        let key_method = resolve_open_key_method(key_method, &pass_key)?;
+        let pass_key = pass_key.to_pass_key();
         let store = Store::open(spec_uri.as_str(), key_method, pass_key, profile).await?;
-        Ok(Arc::new(AskarStore {
-            store: RwLock::new(Some(store)),
-        }))
+        Ok(Arc::new(AskarStore::new(store)))
     }
 
     pub async fn remove(&self, spec_uri: String) -> Result<bool, ErrorCode> {
@@ -75,23 +208,54 @@ impl AskarStoreManager {
         Ok(removed)
     }
 
+    /// Restore a backup written by `AskarStore::export_backup` into a newly provisioned store.
+    /// The target store uses the key method implied by the type of pass key.
+    /// Restoring into an existing store is rejected. A SQLite target file is created exclusively
+    /// and removed if the restore fails; a partially restored store on another backend is kept.
+    /// Like the export, the whole backup is decrypted and decoded in memory.
+    pub async fn restore_backup(
+        &self,
+        path: String,
+        backup_pass_key: String,
+        target_uri: String,
+        target_pass_key: AskarPassKey,
+    ) -> Result<AskarStoreCopyStats, ErrorCode> {
+        restore_backup(&path, &backup_pass_key, &target_uri, target_pass_key).await
+    }
+
     pub async fn migrate_indy_wallet(
         &self,
         spec_uri: String,
         wallet_name: String,
         wallet_key: String,
         kdf_level: String,
-    ) -> Result<(), ErrorCode> {
-        let migrator = IndySdkToAriesAskarMigration::connect(
-            spec_uri.as_str(), wallet_name.as_str(), wallet_key.as_str(), kdf_level.as_str(),
-        ).await?;
-        migrator.migrate().await?;
-        Ok(())
+        dry_run: bool,
+        progress: Option<Box<dyn AskarMigrationProgress>>,
+    ) -> Result<AskarMigrationReport, ErrorCode> {
+        migrate_indy_wallet(
+            &spec_uri,
+            &wallet_name,
+            &wallet_key,
+            &kdf_level,
+            dry_run,
+            progress,
+        )
+        .await
     }
 }
 
 pub struct AskarStore {
     store: RwLock<Option<Store>>, // Option is used to allow for the store to be closed
+    subscriptions: Arc<Subscriptions>,
+}
+
+impl AskarStore {
+    fn new(store: Store) -> Self {
+        Self {
+            store: RwLock::new(Some(store)),
+            subscriptions: Arc::new(Subscriptions::default()),
+        }
+    }
 }
 
 #[uniffi::export(async_runtime = "tokio")]
@@ -120,6 +284,79 @@ impl AskarStore {
         Ok(name)
     }
 
+    pub async fn list_profiles(&self) -> Result<Vec<String>, ErrorCode> {
+        let profiles = self
+            .store
+            .read()
+            .await
+            .as_ref()
+            .ok_or(STORE_CLOSED_ERROR!())?
+            .list_profiles()
+            .await?;
+        Ok(profiles)
+    }
+
+    pub async fn get_default_profile(&self) -> Result<String, ErrorCode> {
+        let name = self
+            .store
+            .read()
+            .await
+            .as_ref()
+            .ok_or(STORE_CLOSED_ERROR!())?
+            .get_default_profile()
+            .await?;
+        Ok(name)
+    }
+
+    pub async fn set_default_profile(&self, profile: String) -> Result<(), ErrorCode> {
+        self.store
+            .read()
+            .await
+            .as_ref()
+            .ok_or(STORE_CLOSED_ERROR!())?
+            .set_default_profile(profile)
+            .await?;
+        Ok(())
+    }
+
+    /// Copy all entries and keys of a profile into a newly created profile.
+    /// Expiry times are preserved for entries written through this wrapper.
+    pub async fn copy_profile(
+        &self,
+        from_profile: String,
+        to_profile: String,
+    ) -> Result<AskarStoreCopyStats, ErrorCode> {
+        let guard = self.store.read().await;
+        let store = guard.as_ref().ok_or(STORE_CLOSED_ERROR!())?;
+        copy_profile_contents(store, from_profile, to_profile).await
+    }
+
+    pub async fn rename_profile(
+        &self,
+        from_profile: String,
+        to_profile: String,
+    ) -> Result<(), ErrorCode> {
+        let guard = self.store.read().await;
+        let store = guard.as_ref().ok_or(STORE_CLOSED_ERROR!())?;
+        if store.get_active_profile() == from_profile {
+            return Err(ErrorCode::Input {
+                message: String::from("Cannot rename the active profile"),
+            });
+        }
+        let is_default = store.get_default_profile().await? == from_profile;
+        copy_profile_contents(store, from_profile.clone(), to_profile.clone()).await?;
+        let result =
+            replace_profile(store, from_profile.clone(), to_profile.clone(), is_default).await;
+        if result.is_err() {
+            // Restore the original profile as the default and remove the copy
+            if is_default {
+                store.set_default_profile(from_profile).await.ok();
+            }
+            store.remove_profile(to_profile).await.ok();
+        }
+        result
+    }
+
     pub async fn remove_profile(&self, profile: String) -> Result<bool, ErrorCode> {
         let removed = self
             .store
@@ -132,16 +369,14 @@ impl AskarStore {
         Ok(removed)
     }
 
+    /// Change the store key. When no key method is given, it is implied by the type of pass key.
     pub async fn rekey(
         &self,
-        key_method: Option<String>,
-        pass_key: Option<String>,
+        key_method: Option<AskarStoreKeyMethod>,
+        pass_key: AskarPassKey,
     ) -> Result<(), ErrorCode> {
-        let key_method = match key_method {
-            Some(method) => StoreKeyMethod::parse_uri(&method)?,
-            None => StoreKeyMethod::default(),
-        };
-        let pass_key = PassKey::from(pass_key.as_deref()).into_owned();
+        let key_method = resolve_key_method(key_method, &pass_key)?;
+        let pass_key = pass_key.to_pass_key();
         self.store
             .write()
             .await
@@ -152,21 +387,116 @@ impl AskarStore {
         Ok(())
     }
 
+    pub async fn copy_to(
+        &self,
+        target_uri: String,
+        key_method: Option<AskarStoreKeyMethod>,
+        pass_key: AskarPassKey,
+        recreate: bool,
+    ) -> Result<AskarStoreCopyStats, ErrorCode> {
+        let key_method = resolve_key_method(key_method, &pass_key)?;
+        let pass_key = pass_key.to_pass_key();
+        let target = self
+            .store
+            .read()
+            .await
+            .as_ref()
+            .ok_or(STORE_CLOSED_ERROR!())?
+            .copy_to(target_uri.as_str(), key_method, pass_key, recreate)
+            .await?;
+        let stats = collect_copy_stats(&target).await;
+        target.close().await?;
+        stats
+    }
+
+    /// Remove the entries of a profile whose expiry time has passed, returning
+    /// the number of entries removed. Only expiry times recorded by this wrapper
+    /// are considered, as reported by `AskarEntry::expiry`, so entries without
+    /// the tag are never removed. Keys are not removed.
+    pub async fn purge_expired(&self, profile: Option<String>) -> Result<i64, ErrorCode> {
+        let removed = self
+            .store
+            .read()
+            .await
+            .as_ref()
+            .ok_or(STORE_CLOSED_ERROR!())?
+            .session(profile)
+            .await?
+            .remove_all(None, Some(expires_within_filter(0)))
+            .await?;
+        Ok(removed)
+    }
+
+    /// Write every profile, entry, tag and key to a passphrase-encrypted backup file.
+    /// Expiry times are preserved for entries written through this wrapper.
+    /// The backup is built in memory, so the whole store, including the secret keys,
+    /// is held in memory several times over while it is encoded and encrypted.
+    pub async fn export_backup(
+        &self,
+        path: String,
+        backup_pass_key: String,
+    ) -> Result<AskarStoreCopyStats, ErrorCode> {
+        let guard = self.store.read().await;
+        let store = guard.as_ref().ok_or(STORE_CLOSED_ERROR!())?;
+        write_backup(store, &path, &backup_pass_key).await
+    }
+
     pub async fn close(&self) -> Result<(), ErrorCode> {
         let store = self.store.write().await.take();
+        self.subscriptions.clear();
         store.ok_or(STORE_CLOSED_ERROR!())?.close().await?;
         Ok(())
     }
 
+    /// Subscribe to insert, replace and remove events for the entries of a category.
+    /// Only writes made through the sessions and transactions of this store are reported,
+    /// and key entries are not included. Events are delivered until the subscription is
+    /// cancelled or the store is closed.
+    /// Tag filters are evaluated in memory, against the tags written by each change, and
+    /// may only use equality, `$neq`, `$in`, `$exist`, `$and`, `$or` and `$not`.
+    /// `remove_all` reports a single remove event without an entry name, whatever the filter.
+    pub async fn subscribe(
+        &self,
+        profile: Option<String>,
+        category: String,
+        tag_filter: Option<Arc<AskarTagFilter>>,
+        listener: Box<dyn AskarEntryListener>,
+    ) -> Result<Arc<AskarSubscription>, ErrorCode> {
+        let profile = match profile {
+            Some(profile) => profile,
+            None => self
+                .store
+                .read()
+                .await
+                .as_ref()
+                .ok_or(STORE_CLOSED_ERROR!())?
+                .get_active_profile()
+                .to_string(),
+        };
+        self.subscriptions
+            .add(profile, category, tag_filter, listener)
+    }
+
+    /// Scan the entries of a category in the order they were inserted, as aries-askar
+    /// does not support other orderings.
     pub async fn scan(
         &self,
         profile: Option<String>,
         category: String,
-        tag_filter: Option<String>,
+        tag_filter: Option<Arc<AskarTagFilter>>,
         offset: Option<i64>,
         limit: Option<i64>,
+        page_size: Option<i64>,
     ) -> Result<Arc<AskarScan>, ErrorCode> {
-        let tag_filter = tag_filter.as_deref().map(TagFilter::from_str).transpose()?;
+        let tag_filter = tag_filter.map(|f| f.filter.clone());
+        let page_size = match page_size {
+            Some(size) if size <= 0 => {
+                return Err(ErrorCode::Input {
+                    message: String::from("Page size must be positive"),
+                })
+            }
+            size => size.map(|size| size as usize),
+        };
         let scan = self
             .store
             .read()
@@ -175,18 +505,35 @@ impl AskarStore {
             .ok_or(STORE_CLOSED_ERROR!())?
             .scan(profile, Some(category), tag_filter, offset, limit)
             .await?;
-        Ok(Arc::new(AskarScan::new(scan)))
+        Ok(Arc::new(AskarScan::new(scan, page_size)))
     }
 
     pub async fn session(&self, profile: Option<String>) -> Result<Arc<AskarSession>, ErrorCode> {
-        let session = self
-            .store
-            .read()
-            .await
-            .as_ref()
-            .ok_or(STORE_CLOSED_ERROR!())?
-            .session(profile)
-            .await?;
-        Ok(Arc::new(AskarSession::new(session)))
+        let guard = self.store.read().await;
+        let store = guard.as_ref().ok_or(STORE_CLOSED_ERROR!())?;
+        let profile = profile.unwrap_or_else(|| store.get_active_profile().to_string());
+        let session = store.session(Some(profile.clone())).await?;
+        Ok(Arc::new(AskarSession::new(
+            session,
+            false,
+            profile,
+            self.subscriptions.clone(),
+        )))
+    }
+
+    pub async fn transaction(
+        &self,
+        profile: Option<String>,
+    ) -> Result<Arc<AskarSession>, ErrorCode> {
+        let guard = self.store.read().await;
+        let store = guard.as_ref().ok_or(STORE_CLOSED_ERROR!())?;
+        let profile = profile.unwrap_or_else(|| store.get_active_profile().to_string());
+        let session = store.transaction(Some(profile.clone())).await?;
+        Ok(Arc::new(AskarSession::new(
+            session,
+            true,
+            profile,
+            self.subscriptions.clone(),
+        )))
     }
 }
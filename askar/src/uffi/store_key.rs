@@ -0,0 +1,151 @@
+use crate::uffi::{
+    encoding::{b58_decode, b58_encode},
+    error::ErrorCode,
+    key::{derive_argon2_key, AskarKdfParams},
+};
+use aries_askar::{
+    crypto::{kdf::argon2::SALT_LENGTH, random::fill_random},
+    PassKey, StoreKeyMethod,
+};
+use sha2::{Digest, Sha256};
+
+const RAW_STORE_KEY_LENGTH: usize = 32;
+
+#[derive(uniffi::Enum, Clone, Copy, Debug, PartialEq)]
+pub enum AskarStoreKeyMethod {
+    Raw,
+    Unprotected,
+    Argon2iMod,
+    Argon2iInt,
+}
+
+impl AskarStoreKeyMethod {
+    fn as_uri(&self) -> &'static str {
+        match self {
+            AskarStoreKeyMethod::Raw => "raw",
+            AskarStoreKeyMethod::Unprotected => "none",
+            AskarStoreKeyMethod::Argon2iMod => "kdf:argon2i:mod",
+            AskarStoreKeyMethod::Argon2iInt => "kdf:argon2i:int",
+        }
+    }
+
+    fn kdf_params(&self) -> Option<AskarKdfParams> {
+        match self {
+            AskarStoreKeyMethod::Argon2iMod => Some(AskarKdfParams::Argon2iModerate),
+            AskarStoreKeyMethod::Argon2iInt => Some(AskarKdfParams::Argon2iInteractive),
+            _ => None,
+        }
+    }
+}
+
+#[derive(uniffi::Enum, Clone, Debug, PartialEq)]
+pub enum AskarPassKey {
+    Empty,
+    Passphrase { passphrase: String },
+    RawKey { key: String },
+}
+
+impl AskarPassKey {
+    /// The key method implied by the type of pass key.
+    fn default_key_method(&self) -> AskarStoreKeyMethod {
+        match self {
+            AskarPassKey::Empty => AskarStoreKeyMethod::Unprotected,
+            AskarPassKey::Passphrase { .. } => AskarStoreKeyMethod::Argon2iMod,
+            AskarPassKey::RawKey { .. } => AskarStoreKeyMethod::Raw,
+        }
+    }
+
+    fn check_key_method(&self, key_method: AskarStoreKeyMethod) -> Result<(), ErrorCode> {
+        let message = match (key_method, self) {
+            (AskarStoreKeyMethod::Raw, AskarPassKey::RawKey { key }) => {
+                return match b58_decode(key) {
+                    Ok(bytes) if bytes.len() == RAW_STORE_KEY_LENGTH => Ok(()),
+                    _ => Err(ErrorCode::Input {
+                        message: String::from("Raw store keys must be 32 bytes encoded as base58"),
+                    }),
+                };
+            }
+            (AskarStoreKeyMethod::Unprotected, AskarPassKey::Empty) => return Ok(()),
+            (AskarStoreKeyMethod::Argon2iMod, AskarPassKey::Passphrase { .. })
+            | (AskarStoreKeyMethod::Argon2iInt, AskarPassKey::Passphrase { .. }) => return Ok(()),
+            (AskarStoreKeyMethod::Raw, _) => "The raw key method requires a raw store key",
+            (AskarStoreKeyMethod::Unprotected, _) => "The unprotected key method takes no pass key",
+            _ => "Argon2i key methods require a passphrase",
+        };
+        Err(ErrorCode::Input {
+            message: String::from(message),
+        })
+    }
+
+    pub(crate) fn to_pass_key(&self) -> PassKey<'static> {
+        let value = match self {
+            AskarPassKey::Empty => None,
+            AskarPassKey::Passphrase { passphrase } => Some(passphrase.as_str()),
+            AskarPassKey::RawKey { key } => Some(key.as_str()),
+        };
+        PassKey::from(value).into_owned()
+    }
+}
+
+/// Resolve the key method used to provision or rekey a store.
+/// When no key method is given, it is implied by the type of pass key.
+pub(crate) fn resolve_key_method(
+    key_method: Option<AskarStoreKeyMethod>,
+    pass_key: &AskarPassKey,
+) -> Result<StoreKeyMethod, ErrorCode> {
+    let key_method = key_method.unwrap_or_else(|| pass_key.default_key_method());
+    pass_key.check_key_method(key_method)?;
+    Ok(StoreKeyMethod::parse_uri(key_method.as_uri())?)
+}
+
+/// Resolve the key method used to open a store, which is detected when not given.
+pub(crate) fn resolve_open_key_method(
+    key_method: Option<AskarStoreKeyMethod>,
+    pass_key: &AskarPassKey,
+) -> Result<Option<StoreKeyMethod>, ErrorCode> {
+    key_method
+        .map(|key_method| resolve_key_method(Some(key_method), pass_key))
+        .transpose()
+}
+
+#[derive(uniffi::Record)]
+pub struct AskarDerivedStoreKey {
+    pub pass_key: AskarPassKey,
+    pub salt: Vec<u8>,
+    pub check: String,
+}
+
+/// Derive a raw store key from a passphrase, for use with the raw key method.
+/// The check value can be kept alongside the salt to validate a passphrase
+/// before the store is opened.
+pub(crate) fn derive_store_key(
+    key_method: AskarStoreKeyMethod,
+    passphrase: &str,
+    salt: Option<Vec<u8>>,
+) -> Result<AskarDerivedStoreKey, ErrorCode> {
+    let kdf_params = key_method.kdf_params().ok_or_else(|| ErrorCode::Input {
+        message: String::from("Store keys can only be derived with an Argon2i key method"),
+    })?;
+    let salt = match salt {
+        Some(salt) if salt.len() < SALT_LENGTH => {
+            return Err(ErrorCode::Input {
+                message: format!("Store key salts must be at least {} bytes", SALT_LENGTH),
+            })
+        }
+        Some(salt) => salt,
+        None => {
+            let mut salt = vec![0u8; SALT_LENGTH];
+            fill_random(&mut salt);
+            salt
+        }
+    };
+    let mut key = [0u8; RAW_STORE_KEY_LENGTH];
+    derive_argon2_key(passphrase, &salt, kdf_params, &mut key)?;
+    Ok(AskarDerivedStoreKey {
+        pass_key: AskarPassKey::RawKey {
+            key: b58_encode(&key),
+        },
+        salt,
+        check: b58_encode(&Sha256::digest(key)),
+    })
+}
@@ -0,0 +1,361 @@
+use crate::uffi::{error::ErrorCode, key::AskarLocalKey};
+use aries_askar::{
+    crypto::{
+        alg::{AesTypes, KeyAlg},
+        random::fill_random,
+    },
+    kms::LocalKey,
+};
+use std::{
+    fs::{self, File},
+    io::{Read, Write},
+    sync::{Arc, Mutex},
+};
+
+// Streams use the STREAM construction over a nonce-based AEAD, in this framed format:
+//
+//   header  = version (1 byte, 1) || segment size (u32 BE) || nonce prefix
+//   stream  = header || segment_0 || ... || segment_n
+//   segment = ciphertext || tag
+//
+// The plaintext is split into segments of `segment size` bytes, and the last segment
+// holds the remaining 0 to `segment size` bytes, so only the last segment is shorter.
+// The nonce of segment i is the nonce prefix || i (u32 BE) || 1 for the last segment
+// and 0 otherwise, which makes the header as long as a nonce of the key algorithm.
+// Each segment is authenticated with the header followed by the caller's aad, so
+// reordered, truncated or extended streams fail to decrypt.
+const STREAM_VERSION: u8 = 1;
+const NONCE_SUFFIX_LENGTH: usize = 5;
+const DEFAULT_SEGMENT_SIZE: u32 = 64 * 1024;
+const MAX_SEGMENT_SIZE: u32 = 16 * 1024 * 1024;
+const FILE_CHUNK_SIZE: usize = 64 * 1024;
+const PARTIAL_SUFFIX: &str = ".partial";
+
+fn truncated_error() -> ErrorCode {
+    ErrorCode::Encryption {
+        message: String::from("Encrypted stream is truncated"),
+    }
+}
+
+fn file_error(err: std::io::Error) -> ErrorCode {
+    ErrorCode::Input {
+        message: format!("Error accessing file: {}", err),
+    }
+}
+
+// The CBC-HMAC algorithms need unpredictable IVs, which STREAM nonces are not
+fn stream_nonce_length(key: &LocalKey) -> Result<usize, ErrorCode> {
+    match key.algorithm() {
+        KeyAlg::Aes(AesTypes::A128Gcm | AesTypes::A256Gcm) | KeyAlg::Chacha20(_) => {
+            Ok(key.aead_params()?.nonce_length)
+        }
+        _ => Err(ErrorCode::Unsupported {
+            message: String::from(
+                "Streaming encryption requires an AES-GCM or ChaCha20-Poly1305 key",
+            ),
+        }),
+    }
+}
+
+struct StreamCipher {
+    nonce_prefix: Vec<u8>,
+    segment_size: usize,
+    tag_length: usize,
+    aad: Vec<u8>,
+    counter: u32,
+}
+
+impl StreamCipher {
+    fn from_header(key: &LocalKey, header: &[u8], aad: &[u8]) -> Result<Self, ErrorCode> {
+        if header[0] != STREAM_VERSION {
+            return Err(ErrorCode::Unsupported {
+                message: format!("Unsupported encrypted stream version: {}", header[0]),
+            });
+        }
+        let segment_size = u32::from_be_bytes([header[1], header[2], header[3], header[4]]);
+        if segment_size == 0 || segment_size > MAX_SEGMENT_SIZE {
+            return Err(ErrorCode::Input {
+                message: format!(
+                    "Stream segment size must be between 1 and {} bytes",
+                    MAX_SEGMENT_SIZE
+                ),
+            });
+        }
+        Ok(Self {
+            nonce_prefix: header[NONCE_SUFFIX_LENGTH..].to_vec(),
+            segment_size: segment_size as usize,
+            tag_length: key.aead_params()?.tag_length,
+            aad: [header, aad].concat(),
+            counter: 0,
+        })
+    }
+
+    fn next_nonce(&mut self, last: bool) -> Result<Vec<u8>, ErrorCode> {
+        let mut nonce = self.nonce_prefix.clone();
+        nonce.extend_from_slice(&self.counter.to_be_bytes());
+        nonce.push(last as u8);
+        self.counter = self
+            .counter
+            .checked_add(1)
+            .ok_or_else(|| ErrorCode::Input {
+                message: String::from("Encrypted stream has too many segments"),
+            })?;
+        Ok(nonce)
+    }
+
+    fn apply(
+        &mut self,
+        key: &LocalKey,
+        encrypt: bool,
+        segment: &[u8],
+        last: bool,
+    ) -> Result<Vec<u8>, ErrorCode> {
+        if encrypt {
+            let nonce = self.next_nonce(last)?;
+            let enc = key.aead_encrypt(segment, &nonce, &self.aad)?;
+            return Ok([enc.ciphertext(), enc.tag()].concat());
+        }
+        if segment.len() < self.tag_length {
+            return Err(truncated_error());
+        }
+        let nonce = self.next_nonce(last)?;
+        let (ciphertext, tag) = segment.split_at(segment.len() - self.tag_length);
+        Ok(key
+            .aead_decrypt((ciphertext, tag), &nonce, &self.aad)?
+            .to_vec())
+    }
+}
+
+struct StreamState {
+    encrypt: bool,
+    aad: Vec<u8>,
+    header: Option<Vec<u8>>, // Header which the encryptor has yet to output
+    cipher: Option<StreamCipher>, // None until the decryptor has read the header
+    buffer: Vec<u8>,
+    finished: bool,
+}
+
+impl StreamState {
+    fn encryptor(
+        key: &LocalKey,
+        aad: Vec<u8>,
+        segment_size: Option<u32>,
+    ) -> Result<Self, ErrorCode> {
+        let nonce_length = stream_nonce_length(key)?;
+        let segment_size = segment_size.unwrap_or(DEFAULT_SEGMENT_SIZE);
+        let mut nonce_prefix = vec![0u8; nonce_length - NONCE_SUFFIX_LENGTH];
+        fill_random(&mut nonce_prefix);
+        let header = [
+            &[STREAM_VERSION][..],
+            &segment_size.to_be_bytes(),
+            &nonce_prefix,
+        ]
+        .concat();
+        let cipher = StreamCipher::from_header(key, &header, &aad)?;
+        Ok(Self {
+            encrypt: true,
+            aad,
+            header: Some(header),
+            cipher: Some(cipher),
+            buffer: Vec::new(),
+            finished: false,
+        })
+    }
+
+    fn decryptor(key: &LocalKey, aad: Vec<u8>) -> Result<Self, ErrorCode> {
+        stream_nonce_length(key)?;
+        Ok(Self {
+            encrypt: false,
+            aad,
+            header: None,
+            cipher: None,
+            buffer: Vec::new(),
+            finished: false,
+        })
+    }
+
+    fn update(&mut self, key: &LocalKey, chunk: &[u8]) -> Result<Vec<u8>, ErrorCode> {
+        self.process(key, chunk, false)
+    }
+
+    fn finalize(&mut self, key: &LocalKey) -> Result<Vec<u8>, ErrorCode> {
+        let output = self.process(key, &[], true);
+        self.finished = true;
+        output
+    }
+
+    fn process(&mut self, key: &LocalKey, chunk: &[u8], last: bool) -> Result<Vec<u8>, ErrorCode> {
+        if self.finished {
+            return Err(ErrorCode::Input {
+                message: String::from("Stream is already finalized"),
+            });
+        }
+        let output = self.process_segments(key, chunk, last);
+        // A stream which failed to decrypt must not produce any further output
+        if output.is_err() {
+            self.finished = true;
+        }
+        output
+    }
+
+    fn process_segments(
+        &mut self,
+        key: &LocalKey,
+        chunk: &[u8],
+        last: bool,
+    ) -> Result<Vec<u8>, ErrorCode> {
+        self.buffer.extend_from_slice(chunk);
+        let mut output = self.header.take().unwrap_or_default();
+        let cipher = match &mut self.cipher {
+            Some(cipher) => cipher,
+            None => {
+                let header_length = stream_nonce_length(key)?;
+                if self.buffer.len() < header_length {
+                    return if last {
+                        Err(truncated_error())
+                    } else {
+                        Ok(output)
+                    };
+                }
+                let header: Vec<u8> = self.buffer.drain(..header_length).collect();
+                self.cipher
+                    .insert(StreamCipher::from_header(key, &header, &self.aad)?)
+            }
+        };
+        let segment_length = match self.encrypt {
+            true => cipher.segment_size,
+            false => cipher.segment_size + cipher.tag_length,
+        };
+        // A full segment is only processed once more data follows it,
+        // as the last segment of the stream is encrypted differently
+        let mut offset = 0;
+        while self.buffer.len() - offset > segment_length {
+            let segment = &self.buffer[offset..offset + segment_length];
+            output.extend(cipher.apply(key, self.encrypt, segment, false)?);
+            offset += segment_length;
+        }
+        self.buffer.drain(..offset);
+        if last {
+            let segment = std::mem::take(&mut self.buffer);
+            output.extend(cipher.apply(key, self.encrypt, &segment, true)?);
+        }
+        Ok(output)
+    }
+}
+
+/// Encrypts or decrypts a payload in segments, without holding it in memory.
+/// Output returned by a decryptor is only authentic once `finalize` succeeds.
+pub struct AskarAeadStream {
+    key: Arc<AskarLocalKey>,
+    state: Mutex<StreamState>,
+}
+
+impl AskarAeadStream {
+    fn with_state<T>(
+        &self,
+        f: impl FnOnce(&mut StreamState, &LocalKey) -> Result<T, ErrorCode>,
+    ) -> Result<T, ErrorCode> {
+        let mut state = self.state.lock().map_err(|_| ErrorCode::Unexpected {
+            message: String::from("Stream state is poisoned"),
+        })?;
+        f(&mut state, &self.key.key)
+    }
+}
+
+#[uniffi::export]
+impl AskarAeadStream {
+    #[uniffi::constructor]
+    pub fn encryptor(
+        key: Arc<AskarLocalKey>,
+        aad: Option<Vec<u8>>,
+        segment_size: Option<u32>,
+    ) -> Result<Arc<Self>, ErrorCode> {
+        let state = StreamState::encryptor(&key.key, aad.unwrap_or_default(), segment_size)?;
+        Ok(Arc::new(Self {
+            key,
+            state: Mutex::new(state),
+        }))
+    }
+
+    #[uniffi::constructor]
+    pub fn decryptor(
+        key: Arc<AskarLocalKey>,
+        aad: Option<Vec<u8>>,
+    ) -> Result<Arc<Self>, ErrorCode> {
+        let state = StreamState::decryptor(&key.key, aad.unwrap_or_default())?;
+        Ok(Arc::new(Self {
+            key,
+            state: Mutex::new(state),
+        }))
+    }
+
+    pub fn update(&self, chunk: Vec<u8>) -> Result<Vec<u8>, ErrorCode> {
+        self.with_state(|state, key| state.update(key, &chunk))
+    }
+
+    pub fn finalize(&self) -> Result<Vec<u8>, ErrorCode> {
+        self.with_state(|state, key| state.finalize(key))
+    }
+}
+
+fn write_stream(
+    state: &mut StreamState,
+    key: &LocalKey,
+    input_path: &str,
+    output_path: &str,
+) -> Result<(), ErrorCode> {
+    let mut input = File::open(input_path).map_err(file_error)?;
+    let mut output = File::create(output_path).map_err(file_error)?;
+    let mut chunk = vec![0u8; FILE_CHUNK_SIZE];
+    loop {
+        let read = input.read(&mut chunk).map_err(file_error)?;
+        if read == 0 {
+            break;
+        }
+        output
+            .write_all(&state.update(key, &chunk[..read])?)
+            .map_err(file_error)?;
+    }
+    output
+        .write_all(&state.finalize(key)?)
+        .map_err(file_error)?;
+    output.sync_all().map_err(file_error)
+}
+
+// The output is written to a working file first, so a failed decryption
+// never leaves unauthenticated plaintext at the output path
+fn transform_file(
+    mut state: StreamState,
+    key: &LocalKey,
+    input_path: &str,
+    output_path: &str,
+) -> Result<(), ErrorCode> {
+    let partial_path = format!("{}{}", output_path, PARTIAL_SUFFIX);
+    match write_stream(&mut state, key, input_path, &partial_path) {
+        Ok(()) => fs::rename(&partial_path, output_path).map_err(file_error),
+        Err(err) => {
+            fs::remove_file(&partial_path).ok();
+            Err(err)
+        }
+    }
+}
+
+pub(crate) fn encrypt_file(
+    key: &LocalKey,
+    input_path: &str,
+    output_path: &str,
+    aad: Vec<u8>,
+) -> Result<(), ErrorCode> {
+    let state = StreamState::encryptor(key, aad, None)?;
+    transform_file(state, key, input_path, output_path)
+}
+
+pub(crate) fn decrypt_file(
+    key: &LocalKey,
+    input_path: &str,
+    output_path: &str,
+    aad: Vec<u8>,
+) -> Result<(), ErrorCode> {
+    let state = StreamState::decryptor(key, aad)?;
+    transform_file(state, key, input_path, output_path)
+}
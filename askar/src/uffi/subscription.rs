@@ -0,0 +1,281 @@
+use crate::uffi::{error::ErrorCode, filter::AskarTagFilter, session::AskarEntryOperation};
+use aries_askar::storage::entry::EntryTag;
+use serde_json::Value;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, Mutex, MutexGuard, Weak,
+};
+
+#[derive(uniffi::Record)]
+pub struct AskarEntryEvent {
+    pub operation: AskarEntryOperation,
+    pub profile: String,
+    pub category: String,
+    /// The name of the changed entry, or None when entries were removed by `remove_all`.
+    pub name: Option<String>,
+}
+
+/// Receives changes to the entries of a store category.
+/// Events are delivered once a write completes, or once a transaction is committed,
+/// on the thread which made the write.
+#[uniffi::export(callback_interface)]
+pub trait AskarEntryListener: Send + Sync {
+    fn on_event(&self, event: AskarEntryEvent);
+}
+
+/// A change to an entry, with the tags used to match subscription filters.
+/// Entries removed together have no name, and match any filter.
+pub(crate) struct EntryChange {
+    pub operation: AskarEntryOperation,
+    pub category: String,
+    pub name: Option<String>,
+    pub tags: Vec<EntryTag>,
+}
+
+struct Subscriber {
+    id: u64,
+    profile: String,
+    category: String,
+    filter: Option<Value>,
+    listener: Box<dyn AskarEntryListener>,
+}
+
+impl Subscriber {
+    fn matches(&self, profile: &str, change: &EntryChange) -> bool {
+        let matches_filter = match (&self.filter, &change.name) {
+            (Some(filter), Some(_)) => matches_query(filter, &change.tags),
+            _ => true,
+        };
+        self.profile == profile && self.category == change.category && matches_filter
+    }
+}
+
+/// The subscribers of a store, shared with the sessions opened from it.
+#[derive(Default)]
+pub(crate) struct Subscriptions {
+    next_id: AtomicU64,
+    subscribers: Mutex<Vec<Arc<Subscriber>>>,
+}
+
+impl Subscriptions {
+    fn subscribers(&self) -> MutexGuard<'_, Vec<Arc<Subscriber>>> {
+        // Listeners are never called with the lock held
+        self.subscribers
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+    }
+
+    pub(crate) fn add(
+        self: &Arc<Self>,
+        profile: String,
+        category: String,
+        tag_filter: Option<Arc<AskarTagFilter>>,
+        listener: Box<dyn AskarEntryListener>,
+    ) -> Result<Arc<AskarSubscription>, ErrorCode> {
+        // Tag filters are matched in memory against the tags of each change
+        let filter = tag_filter
+            .map(|filter| {
+                let json = filter.to_json()?;
+                serde_json::from_str(&json).map_err(|err| ErrorCode::Input {
+                    message: format!("Invalid tag filter: {}", err),
+                })
+            })
+            .transpose()?;
+        if let Some(filter) = &filter {
+            validate_query(filter)?;
+        }
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.subscribers().push(Arc::new(Subscriber {
+            id,
+            profile,
+            category,
+            filter,
+            listener,
+        }));
+        Ok(Arc::new(AskarSubscription {
+            id,
+            subscriptions: Arc::downgrade(self),
+        }))
+    }
+
+    fn remove(&self, id: u64) {
+        self.subscribers().retain(|subscriber| subscriber.id != id);
+    }
+
+    pub(crate) fn clear(&self) {
+        self.subscribers().clear();
+    }
+
+    /// Whether any subscriber may be interested in changes to a category,
+    /// so that the tags of removed entries are only fetched when needed.
+    pub(crate) fn is_watching(&self, profile: &str, category: &str) -> bool {
+        self.subscribers()
+            .iter()
+            .any(|subscriber| subscriber.profile == profile && subscriber.category == category)
+    }
+
+    pub(crate) fn notify(&self, profile: &str, changes: Vec<EntryChange>) {
+        if changes.is_empty() {
+            return;
+        }
+        let subscribers = self.subscribers().clone();
+        for change in changes {
+            for subscriber in subscribers.iter().filter(|s| s.matches(profile, &change)) {
+                subscriber.listener.on_event(AskarEntryEvent {
+                    operation: change.operation,
+                    profile: profile.to_string(),
+                    category: change.category.clone(),
+                    name: change.name.clone(),
+                });
+            }
+        }
+    }
+}
+
+pub struct AskarSubscription {
+    id: u64,
+    subscriptions: Weak<Subscriptions>,
+}
+
+#[uniffi::export]
+impl AskarSubscription {
+    pub fn cancel(&self) {
+        if let Some(subscriptions) = self.subscriptions.upgrade() {
+            subscriptions.remove(self.id);
+        }
+    }
+}
+
+fn tag_values<'a>(tags: &'a [EntryTag], name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+    let (plaintext, name) = match name.strip_prefix('~') {
+        Some(name) => (true, name),
+        None => (false, name),
+    };
+    tags.iter().filter_map(move |tag| match tag {
+        EntryTag::Plaintext(tag_name, value) if plaintext && tag_name == name => {
+            Some(value.as_str())
+        }
+        EntryTag::Encrypted(tag_name, value) if !plaintext && tag_name == name => {
+            Some(value.as_str())
+        }
+        _ => None,
+    })
+}
+
+// Filters are matched in memory, so only the operators whose store semantics are
+// simple to reproduce are accepted. Ordering and $like comparisons depend on the
+// backend, and cannot be applied to encrypted tags, which are stored as ciphertexts.
+fn validate_query(query: &Value) -> Result<(), ErrorCode> {
+    let Value::Object(clauses) = query else {
+        return Err(ErrorCode::input("Invalid tag filter"));
+    };
+    for (key, arg) in clauses {
+        match (key.as_str(), arg) {
+            ("$and" | "$or", Value::Array(queries)) => {
+                queries.iter().try_for_each(validate_query)?
+            }
+            ("$not", query) => validate_query(query)?,
+            ("$exist", Value::String(_)) => (),
+            ("$exist", Value::Array(names)) if names.iter().all(Value::is_string) => (),
+            (name, Value::String(_)) if !name.starts_with('$') => (),
+            (name, Value::Object(ops)) if !name.starts_with('$') => {
+                for (op, arg) in ops {
+                    match (op.as_str(), arg) {
+                        ("$neq", Value::String(_)) => (),
+                        ("$in", Value::Array(values)) if values.iter().all(Value::is_string) => (),
+                        _ => {
+                            return Err(ErrorCode::Unsupported {
+                                message: format!(
+                                    "Tag filter operator {} is not supported for subscriptions",
+                                    op
+                                ),
+                            })
+                        }
+                    }
+                }
+            }
+            (key, _) => {
+                return Err(ErrorCode::Unsupported {
+                    message: format!(
+                        "Tag filter clause {} is not supported for subscriptions",
+                        key
+                    ),
+                })
+            }
+        }
+    }
+    Ok(())
+}
+
+// Evaluate a WQL query, as serialized by a tag filter, against a set of tags
+fn matches_query(query: &Value, tags: &[EntryTag]) -> bool {
+    let Value::Object(clauses) = query else {
+        return false;
+    };
+    clauses.iter().all(|(key, arg)| match (key.as_str(), arg) {
+        ("$and", Value::Array(queries)) => queries.iter().all(|q| matches_query(q, tags)),
+        ("$or", Value::Array(queries)) => queries.iter().any(|q| matches_query(q, tags)),
+        ("$not", query) => !matches_query(query, tags),
+        ("$exist", Value::Array(names)) => names.iter().all(|name| {
+            name.as_str()
+                .is_some_and(|name| tag_values(tags, name).next().is_some())
+        }),
+        ("$exist", Value::String(name)) => tag_values(tags, name).next().is_some(),
+        (name, Value::String(value)) => tag_values(tags, name).any(|v| v == value),
+        (name, Value::Object(ops)) => ops
+            .iter()
+            .all(|(op, arg)| tag_values(tags, name).any(|v| matches_op(v, op, arg))),
+        _ => false,
+    })
+}
+
+fn matches_op(value: &str, op: &str, arg: &Value) -> bool {
+    match (op, arg) {
+        ("$neq", Value::String(arg)) => value != arg,
+        ("$in", Value::Array(args)) => args.iter().any(|arg| arg.as_str() == Some(value)),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn match_tag_filters() {
+        let tags = vec![
+            EntryTag::Encrypted("state".to_owned(), "active".to_owned()),
+            EntryTag::Plaintext("role".to_owned(), "issuer".to_owned()),
+        ];
+        assert!(matches_query(&json!({"state": "active"}), &tags));
+        assert!(!matches_query(&json!({"~state": "active"}), &tags));
+        assert!(matches_query(&json!({"~role": {"$neq": "holder"}}), &tags));
+        assert!(!matches_query(
+            &json!({"~other": {"$neq": "holder"}}),
+            &tags
+        ));
+        assert!(matches_query(
+            &json!({"state": {"$in": ["active", "done"]}}),
+            &tags
+        ));
+        assert!(matches_query(
+            &json!({"$or": [{"state": "done"}, {"$not": {"~role": "holder"}}]}),
+            &tags
+        ));
+        assert!(!matches_query(
+            &json!({"$and": [{"state": "active"}, {"~role": "holder"}]}),
+            &tags
+        ));
+        assert!(matches_query(&json!({"$exist": ["state", "~role"]}), &tags));
+        assert!(!matches_query(&json!({"$exist": "other"}), &tags));
+    }
+
+    #[test]
+    fn validate_tag_filters() {
+        assert!(validate_query(&json!({"state": {"$in": ["a", "b"]}})).is_ok());
+        assert!(validate_query(&json!({"$not": {"~role": {"$neq": "a"}}})).is_ok());
+        assert!(validate_query(&json!({"~role": {"$gt": "a"}})).is_err());
+        assert!(validate_query(&json!({"$or": [{"state": {"$like": "a%"}}]})).is_err());
+    }
+}
@@ -1,10 +1,13 @@
 package org.hyperledger.askar_uniffi
 
 import askar_uniffi.AskarEntryOperation
+import askar_uniffi.AskarEntryTag
 import askar_uniffi.AskarKeyAlg
 import askar_uniffi.AskarSession
 import askar_uniffi.AskarStore
+import askar_uniffi.AskarStoreKeyMethod
 import askar_uniffi.AskarStoreManager
+import askar_uniffi.AskarTagFilter
 import askar_uniffi.Disposable
 import askar_uniffi.LocalKeyFactory
 import kotlinx.serialization.json.*
@@ -28,8 +31,11 @@ class TestAskar {
         put("category", "test category")
         put("name", "test name")
         put("value", "test_value")
-        put("tags", "{\"~plaintag\": \"a\", \"enctag\": \"b\"}")
     }
+    private val testTags = listOf(
+        AskarEntryTag("plaintag", "a", true),
+        AskarEntryTag("enctag", "b", false)
+    )
     private val uriSchema = "sqlite://${getDBDirectory()}"
 
     @BeforeTest
@@ -39,7 +45,7 @@ class TestAskar {
             keyFactory = LocalKeyFactory()
 
             val key = storeManager.generateRawStoreKey(null)
-            store = storeManager.provision("${uriSchema}test.db", "raw", key, null, true)
+            store = storeManager.provision("${uriSchema}test.db", AskarStoreKeyMethod.RAW, key, null, true)
 
             ffiObjects = mutableListOf(store, storeManager, keyFactory)
         }
@@ -88,13 +94,18 @@ class TestAskar {
                 testEntry["category"]!!,
                 testEntry["name"]!!,
                 testEntry["value"]!!.encodeToByteArray(),
-                testEntry["tags"]!!,
+                testTags,
                 null
             )
 
+            val filter = AskarTagFilter.allOf(listOf(
+                AskarTagFilter.isEq("~plaintag", "a"),
+                AskarTagFilter.isEq("enctag", "b")
+            ))
+            ffiObjects.add(filter)
             val count = session.count(
                 testEntry["category"]!!,
-                "{\"~plaintag\": \"a\", \"enctag\": \"b\"}"
+                filter
             )
             assertEquals(1, count)
 
@@ -114,7 +125,7 @@ class TestAskar {
 
             val all = session.fetchAll(
                 testEntry["category"]!!,
-                "{\"~plaintag\": \"a\", \"enctag\": \"b\"}",
+                AskarTagFilter.fromJson("{\"~plaintag\": \"a\", \"enctag\": \"b\"}"),
                 null,
                 false
             )
@@ -129,13 +140,12 @@ class TestAskar {
 
             val newEntry = testEntry.toMutableMap()
             newEntry["value"] = "new value"
-            newEntry["tags"] = "{\"upd\": \"tagval\"}"
             session.update(
                 AskarEntryOperation.REPLACE,
                 newEntry["category"]!!,
                 newEntry["name"]!!,
                 newEntry["value"]!!.encodeToByteArray(),
-                newEntry["tags"]!!,
+                listOf(AskarEntryTag("upd", "tagval", false)),
                 null
             )
 
@@ -180,16 +190,19 @@ class TestAskar {
                 testEntry["category"]!!,
                 testEntry["name"]!!,
                 testEntry["value"]!!.encodeToByteArray(),
-                testEntry["tags"]!!,
+                testTags,
                 null
             )
 
             val scan = store.scan(
                 null,
                 testEntry["category"]!!,
-                "{\"~plaintag\": \"a\", \"enctag\": \"b\"}",
+                AskarTagFilter.fromJson("{\"~plaintag\": \"a\", \"enctag\": \"b\"}"),
                 null,
-                null
+                null,
+                null,
+                null,
+                false
             )
             ffiObjects.add(scan)
 
@@ -213,14 +226,14 @@ class TestAskar {
             val keypair = keyFactory.generate(AskarKeyAlg.ED25519, false)
             ffiObjects.add(keypair)
             val keyName = "test_key"
-            session.insertKey(keyName, keypair, "metadata", "{\"a\": \"b\"}", null)
+            session.insertKey(keyName, keypair, "metadata", null, listOf(AskarEntryTag("a", "b", false)), null)
 
             var key = session.fetchKey(keyName, false)
             ffiObjects.add(key!!)
             assertEquals(keyName, key.name())
             assertEquals("b", key.tags()["a"])
 
-            session.updateKey(keyName, "new metadata", "{\"a\": \"c\"}", null)
+            session.updateKey(keyName, "new metadata", listOf(AskarEntryTag("a", "c", false)), null)
             key = session.fetchKey(keyName, false)
             ffiObjects.add(key!!)
             assertEquals(keyName, key.name())
@@ -232,7 +245,7 @@ class TestAskar {
             val keylist = session.fetchAllKeys(
                 "ed25519",
                 thumbprint,
-                "{\"a\": \"c\"}",
+                AskarTagFilter.isEq("a", "c"),
                 -1,
                 false
             )
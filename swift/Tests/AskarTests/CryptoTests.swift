@@ -4,6 +4,7 @@ import XCTest
 final class CryptoTests: XCTestCase {
     let keyFactory = LocalKeyFactory()
     let crypto = AskarCrypto()
+    let didcomm = AskarDidCommV1()
 
     func testCryptoBoxSeal() throws {
         let key = try keyFactory.generate(alg: .x25519, ephemeral: false)
@@ -22,4 +23,45 @@ final class CryptoTests: XCTestCase {
         let dec = try crypto.boxOpen(receiverKey: receiverKey, senderKey: senderKey, message: enc, nonce: nonce)
         XCTAssertEqual(message, Data(dec))
     }
+
+    func testDidCommV1Authcrypt() throws {
+        let senderKey = try keyFactory.generate(alg: .ed25519, ephemeral: false)
+        let receiverKey = try keyFactory.generate(alg: .ed25519, ephemeral: false)
+        let receiverVerkey = try keyFactory.generate(alg: .ed25519, ephemeral: false)
+        let message = "test message".data(using: .utf8)!
+        let recipients = [
+            try receiverKey.toIndyVerkey(abbreviated: false),
+            try receiverVerkey.toIndyVerkey(abbreviated: false),
+        ]
+        let envelope = try didcomm.pack(message: message, recipientVerkeys: recipients, senderKey: senderKey)
+        XCTAssertEqual(try didcomm.recipientVerkeys(envelope: envelope), recipients)
+
+        let unpacked = try didcomm.unpack(envelope: envelope, keyResolver: TestDidCommKeyResolver(keys: [receiverKey]))
+        XCTAssertEqual(unpacked.message, message)
+        XCTAssertEqual(unpacked.recipientVerkey, recipients[0])
+        XCTAssertEqual(unpacked.senderVerkey, try senderKey.toIndyVerkey(abbreviated: false))
+    }
+
+    func testDidCommV1Anoncrypt() throws {
+        let receiverKey = try keyFactory.generate(alg: .ed25519, ephemeral: false)
+        let message = "test message".data(using: .utf8)!
+        let recipients = [try receiverKey.toIndyVerkey(abbreviated: false)]
+        let envelope = try didcomm.pack(message: message, recipientVerkeys: recipients, senderKey: nil)
+
+        let unpacked = try didcomm.unpack(envelope: envelope, keyResolver: TestDidCommKeyResolver(keys: [receiverKey]))
+        XCTAssertEqual(unpacked.message, message)
+        XCTAssertNil(unpacked.senderVerkey)
+    }
+}
+
+class TestDidCommKeyResolver: AskarDidCommKeyResolver {
+    let keys: [AskarLocalKey]
+
+    init(keys: [AskarLocalKey]) {
+        self.keys = keys
+    }
+
+    func resolveKey(verkey: String) throws -> AskarLocalKey? {
+        try keys.first { try $0.toIndyVerkey(abbreviated: false) == verkey }
+    }
 }
@@ -203,4 +203,57 @@ final class JoseEcdhTests: XCTestCase {
             tag: nil)
         XCTAssertEqual(try cekRecv2.toJwkSecret(), try cek.toJwkSecret())
     }
+
+    func testJweAuthcryptJson() throws {
+        let jwe = AskarJwe()
+        let alice = try keyFactory.generate(alg: .x25519, ephemeral: false)
+        let bob = try keyFactory.generate(alg: .x25519, ephemeral: false)
+        let carol = try keyFactory.generate(alg: .x25519, ephemeral: false)
+        let message = "Hello there".data(using: .utf8)!
+        let recipients = [
+            AskarJweRecipient(kid: "did:example:bob#key-1", key: bob),
+            AskarJweRecipient(kid: "did:example:carol#key-1", key: carol),
+        ]
+        let encrypted = try jwe.encryptJson(
+            plaintext: message, alg: .ecdh1puA256kw, enc: .a256CbcHs512, recipients: recipients,
+            senderKid: "did:example:alice#key-1", senderKey: alice)
+        XCTAssertEqual(try jwe.recipientKids(jwe: encrypted), recipients.map { $0.kid })
+
+        let decrypted = try jwe.decrypt(jwe: encrypted, recipient: recipients[1], senderKey: alice)
+        XCTAssertEqual(message, decrypted)
+    }
+
+    func testJweAuthcryptRequiresCbcHmac() throws {
+        let jwe = AskarJwe()
+        let alice = try keyFactory.generate(alg: .x25519, ephemeral: false)
+        let bob = try keyFactory.generate(alg: .x25519, ephemeral: false)
+        let recipient = AskarJweRecipient(kid: "did:example:bob#key-1", key: bob)
+        let message = "Hello there".data(using: .utf8)!
+        for enc in [AskarJweEnc.a256Gcm, AskarJweEnc.xc20p] {
+            XCTAssertThrowsError(try jwe.encryptJson(
+                plaintext: message, alg: .ecdh1puA256kw, enc: enc, recipients: [recipient],
+                senderKid: "did:example:alice#key-1", senderKey: alice)) { error in
+                guard case ErrorCode.Unsupported = error else {
+                    return XCTFail("Unexpected error: \(error)")
+                }
+            }
+            XCTAssertThrowsError(try jwe.encryptCompact(
+                plaintext: message, alg: .ecdh1puA256kw, enc: enc, recipient: recipient,
+                senderKid: "did:example:alice#key-1", senderKey: alice))
+        }
+    }
+
+    func testJweAnoncryptCompact() throws {
+        let jwe = AskarJwe()
+        let bob = try keyFactory.generate(alg: .p256, ephemeral: false)
+        let recipient = AskarJweRecipient(kid: "did:example:bob#key-1", key: bob)
+        let message = "Hello there".data(using: .utf8)!
+        let encrypted = try jwe.encryptCompact(
+            plaintext: message, alg: .ecdhEsA256kw, enc: .xc20p, recipient: recipient,
+            senderKid: nil, senderKey: nil)
+        XCTAssertEqual(encrypted.split(separator: ".").count, 5)
+
+        let decrypted = try jwe.decrypt(jwe: encrypted, recipient: recipient, senderKey: nil)
+        XCTAssertEqual(message, decrypted)
+    }
 }
@@ -67,4 +67,119 @@ final class KeyTests: XCTestCase {
         XCTAssertEqual(jwk2["kty"] as! String, "OKP")
         XCTAssertEqual(jwk2["crv"] as! String, "Ed25519")
     }
+
+    func testJws() throws {
+        let jws = AskarJws()
+        let message = "test message".data(using: .utf8)!
+        for alg in [AskarKeyAlg.ed25519, .p256, .k256, .p384] {
+            let key = try keyFactory.generate(alg: alg, ephemeral: false)
+            let compact = try jws.signCompact(payload: message, key: key, kid: "key-1", detached: false)
+            XCTAssertTrue(try jws.verify(jws: compact, key: key, detachedPayload: nil))
+            XCTAssertEqual(try jws.payload(jws: compact), message)
+
+            let detached = try jws.signFlattened(payload: message, key: key, kid: nil, detached: true)
+            XCTAssertNil(try jws.payload(jws: detached))
+            XCTAssertTrue(try jws.verifyWithJwk(jws: detached, jwk: key.toJwkPublic(alg: nil), detachedPayload: message))
+            XCTAssertFalse(try jws.verify(jws: detached, key: key, detachedPayload: Data("other".utf8)))
+        }
+    }
+
+    func testDidKey() throws {
+        let key = try keyFactory.fromJwk(jwk:
+            "{\"kty\":\"OKP\",\"crv\":\"Ed25519\",\"x\":\"O2onvM62pC1io6jQKm8Nc2UyFXcd4kOmOsBIoYtZ2ik\"}")
+        let did = "did:key:z6MkiTBz1ymuepAQ4HEHYSF1H8quG5GLVVQR3djdX3mDooWp"
+        XCTAssertEqual(try key.toDidKey(), did)
+        XCTAssertEqual(try key.toMultibasePublic(), "z6MkiTBz1ymuepAQ4HEHYSF1H8quG5GLVVQR3djdX3mDooWp")
+
+        let parsed = try keyFactory.fromDidKey(did: did + "#z6MkiTBz1ymuepAQ4HEHYSF1H8quG5GLVVQR3djdX3mDooWp")
+        XCTAssertEqual(try parsed.toPublicBytes(), try key.toPublicBytes())
+
+        let agreement = try keyFactory.keyAgreementFromDidKey(did: did)
+        XCTAssertEqual(agreement.algorithm(), .x25519)
+        XCTAssertEqual(try agreement.toPublicBytes(), try key.convertKey(alg: .x25519).toPublicBytes())
+
+        for alg in [AskarKeyAlg.x25519, .p256, .p384, .k256, .bls12381g2] {
+            let generated = try keyFactory.generate(alg: alg, ephemeral: false)
+            let roundTrip = try keyFactory.fromMultibase(value: generated.toMultibasePublic())
+            XCTAssertEqual(roundTrip.algorithm(), alg)
+            XCTAssertEqual(try roundTrip.toPublicBytes(), try generated.toPublicBytes())
+        }
+    }
+
+    func testIndyVerkey() throws {
+        let key = try keyFactory.fromJwk(jwk:
+            "{\"kty\":\"OKP\",\"crv\":\"Ed25519\",\"x\":\"O2onvM62pC1io6jQKm8Nc2UyFXcd4kOmOsBIoYtZ2ik\"}")
+        XCTAssertEqual(try key.toIndyVerkey(abbreviated: false), "4zvwRjXUKGfvwnParsHAS3HuSVzV5cA4McphgmoCtajS")
+        XCTAssertEqual(try key.toIndyVerkey(abbreviated: true), "~DVmn2SS4dgWBnGuQAYYDZJ")
+        XCTAssertEqual(try key.toIndyDid(), "8LXvA8e8DEoLfxxdtohXHU")
+
+        let full = try keyFactory.fromIndyVerkey(verkey: "4zvwRjXUKGfvwnParsHAS3HuSVzV5cA4McphgmoCtajS", did: nil)
+        XCTAssertEqual(try full.toPublicBytes(), try key.toPublicBytes())
+        let abbreviated = try keyFactory.fromIndyVerkey(verkey: "~DVmn2SS4dgWBnGuQAYYDZJ", did: "did:sov:8LXvA8e8DEoLfxxdtohXHU")
+        XCTAssertEqual(try abbreviated.toPublicBytes(), try key.toPublicBytes())
+        XCTAssertThrowsError(try keyFactory.fromIndyVerkey(verkey: "~DVmn2SS4dgWBnGuQAYYDZJ", did: nil))
+    }
+
+    func testExportEncrypted() throws {
+        let key = try keyFactory.generate(alg: .ed25519, ephemeral: false)
+        let blob = try key.exportEncrypted(passphrase: "test passphrase", kdfParams: .argon2iInteractive, enc: .a256Gcm)
+        XCTAssertEqual(blob.split(separator: ".", omittingEmptySubsequences: false).count, 5)
+
+        let imported = try keyFactory.importEncrypted(blob: blob, passphrase: "test passphrase")
+        XCTAssertEqual(imported.algorithm(), .ed25519)
+        XCTAssertEqual(try imported.toSecretBytes(), try key.toSecretBytes())
+        XCTAssertThrowsError(try keyFactory.importEncrypted(blob: blob, passphrase: "wrong passphrase"))
+    }
+
+    func testAeadStream() throws {
+        let key = try keyFactory.generate(alg: .c20p, ephemeral: false)
+        let message = Data((0..<1000).map { UInt8($0 % 256) })
+        let aad = Data("stream aad".utf8)
+
+        let encryptor = try AskarAeadStream.encryptor(key: key, aad: aad, segmentSize: 64)
+        var encrypted = Data()
+        for offset in stride(from: 0, to: message.count, by: 100) {
+            encrypted.append(try encryptor.update(chunk: message.subdata(in: offset..<min(offset + 100, message.count))))
+        }
+        encrypted.append(try encryptor.finalize())
+        XCTAssertThrowsError(try encryptor.update(chunk: message))
+
+        let decryptor = try AskarAeadStream.decryptor(key: key, aad: aad)
+        var decrypted = Data()
+        for offset in stride(from: 0, to: encrypted.count, by: 77) {
+            decrypted.append(try decryptor.update(chunk: encrypted.subdata(in: offset..<min(offset + 77, encrypted.count))))
+        }
+        decrypted.append(try decryptor.finalize())
+        XCTAssertEqual(decrypted, message)
+
+        let truncated = try AskarAeadStream.decryptor(key: key, aad: aad)
+        _ = try truncated.update(chunk: encrypted.prefix(encrypted.count - 20))
+        XCTAssertThrowsError(try truncated.finalize())
+
+        let wrongAad = try AskarAeadStream.decryptor(key: key, aad: nil)
+        XCTAssertThrowsError(try wrongAad.update(chunk: encrypted))
+        XCTAssertThrowsError(try AskarAeadStream.encryptor(key: try keyFactory.generate(alg: .a128CbcHs256, ephemeral: false), aad: nil, segmentSize: nil))
+    }
+
+    func testAeadFile() throws {
+        let key = try keyFactory.generate(alg: .a256Gcm, ephemeral: false)
+        let dir = FileManager.default.temporaryDirectory
+        let inputPath = dir.appendingPathComponent("stream_input").path
+        let encryptedPath = dir.appendingPathComponent("stream_encrypted").path
+        let outputPath = dir.appendingPathComponent("stream_output").path
+        let message = Data((0..<200_000).map { UInt8($0 % 251) })
+        try message.write(to: URL(fileURLWithPath: inputPath))
+
+        try key.aeadEncryptFile(inputPath: inputPath, outputPath: encryptedPath, aad: nil)
+        try key.aeadDecryptFile(inputPath: encryptedPath, outputPath: outputPath, aad: nil)
+        XCTAssertEqual(try Data(contentsOf: URL(fileURLWithPath: outputPath)), message)
+
+        try FileManager.default.removeItem(atPath: outputPath)
+        XCTAssertThrowsError(try key.aeadDecryptFile(inputPath: encryptedPath, outputPath: outputPath, aad: Data("other".utf8)))
+        XCTAssertFalse(FileManager.default.fileExists(atPath: outputPath))
+
+        for path in [inputPath, encryptedPath] {
+            try FileManager.default.removeItem(atPath: path)
+        }
+    }
 }
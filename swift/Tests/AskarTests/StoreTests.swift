@@ -11,7 +11,10 @@ final class StoreTests: XCTestCase {
         "category": "test category",
         "name": "test name",
         "value": "test_value",
-        "tags": "{\"~plaintag\": \"a\", \"enctag\": \"b\"}" // We cannot support json tag
+    ]
+    let TEST_TAGS = [
+        AskarEntryTag(name: "plaintag", value: "a", plaintext: true),
+        AskarEntryTag(name: "enctag", value: "b", plaintext: false),
     ]
     let URI_SCHEMA = "sqlite://"
 
@@ -20,7 +23,7 @@ final class StoreTests: XCTestCase {
 
         let storeURL = temporaryDirectoryURL.appendingPathComponent("test.db")
         let key = try storeManager.generateRawStoreKey(seed: nil)
-        store = try await storeManager.provision(specUri: URI_SCHEMA + storeURL.path, keyMethod: "raw", passKey: key, profile: nil, recreate: true)
+        store = try await storeManager.provision(specUri: URI_SCHEMA + storeURL.path, keyMethod: .raw, passKey: key, profile: nil, recreate: true)
     }
 
     override func tearDown() async throws {
@@ -48,11 +51,15 @@ final class StoreTests: XCTestCase {
             category: TEST_ENTRY["category"]!,
             name: TEST_ENTRY["name"]!,
             value: Data(TEST_ENTRY["value"]!.utf8),
-            tags: TEST_ENTRY["tags"]!,
+            tags: TEST_TAGS,
             expiryMs: nil)
 
+        let filter = AskarTagFilter.allOf(filters: [
+            AskarTagFilter.isEq(name: "~plaintag", value: "a"),
+            AskarTagFilter.isEq(name: "enctag", value: "b"),
+        ])
         let count = try await session.count(category: TEST_ENTRY["category"]!,
-                                            tagFilter: "{\"~plaintag\": \"a\", \"enctag\": \"b\"}")
+                                            tagFilter: filter)
         XCTAssertEqual(count, 1)
 
         if let found = try await session.fetch(category: TEST_ENTRY["category"]!,
@@ -63,12 +70,16 @@ final class StoreTests: XCTestCase {
             let tags = found.tags()
             XCTAssertEqual(tags["plaintag"], "a")
             XCTAssertEqual(tags["enctag"], "b")
+            let tagsList = found.tagsList()
+            XCTAssertEqual(tagsList.count, 2)
+            XCTAssertTrue(tagsList.contains { $0.name == "plaintag" && $0.value == "a" && $0.plaintext })
+            XCTAssertTrue(tagsList.contains { $0.name == "enctag" && $0.value == "b" && !$0.plaintext })
         } else {
             XCTFail("Entry not found")
         }
 
         let all = try await session.fetchAll(category: TEST_ENTRY["category"]!,
-                                             tagFilter: "{\"~plaintag\": \"a\", \"enctag\": \"b\"}",
+                                             tagFilter: AskarTagFilter.fromJson(json: "{\"~plaintag\": \"a\", \"enctag\": \"b\"}"),
                                              limit: nil,
                                              forUpdate: false)
         XCTAssertEqual(all.count, 1)
@@ -79,13 +90,12 @@ final class StoreTests: XCTestCase {
 
         var newEntry = TEST_ENTRY
         newEntry["value"] = "new value"
-        newEntry["tags"] = "{\"upd\": \"tagval\"}"
         try await session.update(
             operation: .replace,
             category: TEST_ENTRY["category"]!,
             name: TEST_ENTRY["name"]!,
             value: Data(newEntry["value"]!.utf8),
-            tags: newEntry["tags"]!,
+            tags: [AskarEntryTag(name: "upd", value: "tagval", plaintext: false)],
             expiryMs: nil)
 
         if let found = try await session.fetch(category: TEST_ENTRY["category"]!,
@@ -111,6 +121,259 @@ final class StoreTests: XCTestCase {
         XCTAssertNil(empty)
     }
 
+    func testUpdateBatch() async throws {
+        session = try await store.session(profile: nil)
+        var updates = (0..<5).map { idx in
+            AskarEntryUpdate(
+                operation: .insert,
+                category: TEST_ENTRY["category"]!,
+                name: "name\(idx)",
+                value: Data(TEST_ENTRY["value"]!.utf8),
+                tags: TEST_TAGS,
+                expiryMs: nil)
+        }
+        updates.append(updates[0])
+        let results = try await session.updateBatch(updates: updates)
+        XCTAssertEqual(results.count, 6)
+        XCTAssertTrue(results[0..<5].allSatisfy { $0.error == nil })
+        XCTAssertNotNil(results[5].error)
+
+        let count = try await session.count(category: TEST_ENTRY["category"]!, tagFilter: nil)
+        XCTAssertEqual(count, 5)
+    }
+
+    func testReservedTags() async throws {
+        session = try await store.session(profile: nil)
+        let reserved = [AskarEntryTag(name: "askar:expiry", value: "0", plaintext: true)]
+        do {
+            try await session.update(operation: .insert, category: "reserved", name: "entry", value: Data(), tags: reserved, expiryMs: nil)
+            XCTFail("Reserved plaintext tags should be rejected")
+        } catch {}
+        do {
+            try await session.insertJson(category: "reserved", name: "json", json: "{}", tags: reserved, expiryMs: nil)
+            XCTFail("Reserved plaintext tags should be rejected")
+        } catch {}
+        let results = try await session.updateBatch(updates: [
+            AskarEntryUpdate(operation: .insert, category: "reserved", name: "batch", value: Data(), tags: reserved, expiryMs: nil),
+        ])
+        XCTAssertNotNil(results[0].error)
+        XCTAssertEqual(try await session.count(category: "reserved", tagFilter: nil), 0)
+
+        let keypair = try keyFactory.generate(alg: .ed25519, ephemeral: false)
+        do {
+            try await session.insertKey(name: "reserved", key: keypair, metadata: nil, reference: nil, tags: reserved, expiryMs: nil)
+            XCTFail("Reserved plaintext tags should be rejected")
+        } catch {}
+        try await session.insertKey(name: "reserved", key: keypair, metadata: nil, reference: nil, tags: nil, expiryMs: nil)
+        do {
+            try await session.updateKey(name: "reserved", metadata: nil, tags: reserved, expiryMs: nil)
+            XCTFail("Reserved plaintext tags should be rejected")
+        } catch {}
+
+        let encrypted = [AskarEntryTag(name: "askar:expiry", value: "0", plaintext: false)]
+        try await session.update(operation: .insert, category: "reserved", name: "encrypted", value: Data(), tags: encrypted, expiryMs: nil)
+    }
+
+    func testJsonEntries() async throws {
+        session = try await store.session(profile: nil)
+        try await session.insertJson(category: "json", name: "record", json: "{\"state\":\"offer\",\"thread\":{\"id\":\"1\"},\"extra\":true}", tags: TEST_TAGS, expiryMs: nil)
+        do {
+            try await session.insertJson(category: "json", name: "invalid", json: "{not json", tags: nil, expiryMs: nil)
+            XCTFail("Invalid JSON should not be inserted")
+        } catch {}
+
+        let patched = try await session.patchJson(category: "json", name: "record", jsonMergePatch: "{\"state\":\"done\",\"thread\":{\"pthid\":\"2\"},\"extra\":null}")
+        let found = try await session.fetch(category: "json", name: "record", forUpdate: false)!
+        XCTAssertEqual(try found.valueAsJson(), patched)
+        let value = try JSONSerialization.jsonObject(with: Data(try found.valueAsString().utf8)) as! [String: Any]
+        XCTAssertEqual(value["state"] as? String, "done")
+        XCTAssertEqual(value["thread"] as? [String: String], ["id": "1", "pthid": "2"])
+        XCTAssertNil(value["extra"])
+        XCTAssertEqual(found.tags(), ["plaintag": "a", "enctag": "b"])
+
+        try await session.replaceJson(category: "json", name: "record", json: "[1,2]", tags: nil, expiryMs: nil)
+        let replaced = try await session.fetch(category: "json", name: "record", forUpdate: false)!
+        XCTAssertEqual(try replaced.valueAsJson(), "[1,2]")
+    }
+
+    func testTransaction() async throws {
+        let txn = try await store.transaction(profile: nil)
+        XCTAssertTrue(txn.isTransaction())
+        try await txn.update(
+            operation: .insert,
+            category: TEST_ENTRY["category"]!,
+            name: TEST_ENTRY["name"]!,
+            value: Data(TEST_ENTRY["value"]!.utf8),
+            tags: TEST_TAGS,
+            expiryMs: nil)
+        try await txn.rollback()
+
+        session = try await store.session(profile: nil)
+        XCTAssertFalse(session.isTransaction())
+        var count = try await session.count(category: TEST_ENTRY["category"]!, tagFilter: nil)
+        XCTAssertEqual(count, 0)
+
+        let txn2 = try await store.transaction(profile: nil)
+        try await txn2.update(
+            operation: .insert,
+            category: TEST_ENTRY["category"]!,
+            name: TEST_ENTRY["name"]!,
+            value: Data(TEST_ENTRY["value"]!.utf8),
+            tags: TEST_TAGS,
+            expiryMs: nil)
+        try await txn2.commit()
+
+        count = try await session.count(category: TEST_ENTRY["category"]!, tagFilter: nil)
+        XCTAssertEqual(count, 1)
+    }
+
+    func testCopy() async throws {
+        session = try await store.session(profile: nil)
+        try await session.update(
+            operation: .insert,
+            category: TEST_ENTRY["category"]!,
+            name: TEST_ENTRY["name"]!,
+            value: Data(TEST_ENTRY["value"]!.utf8),
+            tags: TEST_TAGS,
+            expiryMs: nil)
+        let keypair = try keyFactory.generate(alg: .ed25519, ephemeral: false)
+        try await session.insertKey(name: "test_key", key: keypair, metadata: nil, reference: nil, tags: nil, expiryMs: nil)
+
+        let copyURL = temporaryDirectoryURL.appendingPathComponent("test_copy.db")
+        let copyKey = try storeManager.generateRawStoreKey(seed: nil)
+        let stats = try await store.copyTo(targetUri: URI_SCHEMA + copyURL.path, keyMethod: .raw, passKey: copyKey, recreate: true)
+        XCTAssertEqual(stats.profiles, 1)
+        XCTAssertEqual(stats.entries, 1)
+        XCTAssertEqual(stats.keys, 1)
+
+        let copied = try await storeManager.open(specUri: URI_SCHEMA + copyURL.path, keyMethod: .raw, passKey: copyKey, profile: nil)
+        let copiedSession = try await copied.session(profile: nil)
+        let found = try await copiedSession.fetch(category: TEST_ENTRY["category"]!, name: TEST_ENTRY["name"]!, forUpdate: false)
+        XCTAssertEqual(String(bytes: found!.value(), encoding: .utf8), TEST_ENTRY["value"])
+        try await copiedSession.close()
+        try await copied.close()
+        _ = try await storeManager.remove(specUri: URI_SCHEMA + copyURL.path)
+    }
+
+    func testBackup() async throws {
+        session = try await store.session(profile: nil)
+        try await session.update(
+            operation: .insert,
+            category: TEST_ENTRY["category"]!,
+            name: TEST_ENTRY["name"]!,
+            value: Data(TEST_ENTRY["value"]!.utf8),
+            tags: TEST_TAGS,
+            expiryMs: nil)
+        let keypair = try keyFactory.generate(alg: .ed25519, ephemeral: false)
+        try await session.insertKey(name: "test_key", key: keypair, metadata: "meta", reference: nil, tags: nil, expiryMs: nil)
+        _ = try await store.createProfile(profile: "other")
+
+        let backupURL = temporaryDirectoryURL.appendingPathComponent("test_backup.jwe")
+        let exported = try await store.exportBackup(path: backupURL.path, backupPassKey: "backup pass")
+        XCTAssertEqual(exported.profiles, 2)
+        XCTAssertEqual(exported.entries, 1)
+        XCTAssertEqual(exported.keys, 1)
+
+        let restoreURL = temporaryDirectoryURL.appendingPathComponent("test_restore.db")
+        _ = try await storeManager.remove(specUri: URI_SCHEMA + restoreURL.path)
+        do {
+            _ = try await storeManager.restoreBackup(path: backupURL.path, backupPassKey: "wrong pass", targetUri: URI_SCHEMA + restoreURL.path, targetPassKey: .passphrase(passphrase: "restore pass"))
+            XCTFail("Restoring with the wrong backup pass key should fail")
+        } catch {}
+        let restored = try await storeManager.restoreBackup(path: backupURL.path, backupPassKey: "backup pass", targetUri: URI_SCHEMA + restoreURL.path, targetPassKey: .passphrase(passphrase: "restore pass"))
+        XCTAssertEqual(restored.profiles, 2)
+        XCTAssertEqual(restored.entries, 1)
+        XCTAssertEqual(restored.keys, 1)
+
+        let restoredStore = try await storeManager.open(specUri: URI_SCHEMA + restoreURL.path, keyMethod: nil, passKey: .passphrase(passphrase: "restore pass"), profile: nil)
+        XCTAssertEqual(Set(try await restoredStore.listProfiles()), Set(try await store.listProfiles()))
+        let restoredSession = try await restoredStore.session(profile: nil)
+        let found = try await restoredSession.fetch(category: TEST_ENTRY["category"]!, name: TEST_ENTRY["name"]!, forUpdate: false)
+        XCTAssertEqual(String(bytes: found!.value(), encoding: .utf8), TEST_ENTRY["value"])
+        XCTAssertEqual(found!.tags(), ["plaintag": "a", "enctag": "b"])
+        let key = try await restoredSession.fetchKey(name: "test_key", forUpdate: false)
+        XCTAssertEqual(key!.metadata(), "meta")
+        XCTAssertEqual(try key!.loadLocalKey().toSecretBytes(), try keypair.toSecretBytes())
+        try await restoredSession.close()
+        try await restoredStore.close()
+        do {
+            _ = try await storeManager.restoreBackup(path: backupURL.path, backupPassKey: "backup pass", targetUri: URI_SCHEMA + restoreURL.path, targetPassKey: .passphrase(passphrase: "restore pass"))
+            XCTFail("Restoring into an existing store should fail")
+        } catch {}
+        do {
+            _ = try await storeManager.restoreBackup(path: backupURL.path, backupPassKey: "backup pass", targetUri: URI_SCHEMA + restoreURL.path, targetPassKey: .passphrase(passphrase: "other pass"))
+            XCTFail("Restoring into a store with another key should fail")
+        } catch {}
+        XCTAssertNotNil(try? await storeManager.open(specUri: URI_SCHEMA + restoreURL.path, keyMethod: nil, passKey: .passphrase(passphrase: "restore pass"), profile: nil).close())
+        _ = try await storeManager.remove(specUri: URI_SCHEMA + restoreURL.path)
+
+        let foreign = Data("not a store".utf8)
+        try foreign.write(to: restoreURL)
+        do {
+            _ = try await storeManager.restoreBackup(path: backupURL.path, backupPassKey: "backup pass", targetUri: URI_SCHEMA + restoreURL.path, targetPassKey: .passphrase(passphrase: "restore pass"))
+            XCTFail("Restoring over an existing file should fail")
+        } catch {}
+        XCTAssertEqual(try Data(contentsOf: restoreURL), foreign)
+        try FileManager.default.removeItem(at: restoreURL)
+        try FileManager.default.removeItem(at: backupURL)
+    }
+
+    func testStoreKeyMethods() async throws {
+        let storeURL = temporaryDirectoryURL.appendingPathComponent("test_key_methods.db")
+        let passphrase = AskarPassKey.passphrase(passphrase: "test passphrase")
+        do {
+            _ = try await storeManager.provision(specUri: URI_SCHEMA + storeURL.path, keyMethod: .raw, passKey: passphrase, profile: nil, recreate: true)
+            XCTFail("A passphrase should not be accepted for the raw key method")
+        } catch {}
+
+        let derived = try storeManager.deriveStoreKey(keyMethod: .argon2iInt, passphrase: "test passphrase", salt: nil)
+        let again = try storeManager.deriveStoreKey(keyMethod: .argon2iInt, passphrase: "test passphrase", salt: derived.salt)
+        XCTAssertEqual(again.passKey, derived.passKey)
+        XCTAssertEqual(again.check, derived.check)
+        let wrong = try storeManager.deriveStoreKey(keyMethod: .argon2iInt, passphrase: "wrong passphrase", salt: derived.salt)
+        XCTAssertNotEqual(wrong.check, derived.check)
+
+        let keyed = try await storeManager.provision(specUri: URI_SCHEMA + storeURL.path, keyMethod: nil, passKey: derived.passKey, profile: nil, recreate: true)
+        try await keyed.rekey(keyMethod: .argon2iInt, passKey: passphrase)
+        try await keyed.close()
+        let reopened = try await storeManager.open(specUri: URI_SCHEMA + storeURL.path, keyMethod: .argon2iInt, passKey: passphrase, profile: nil)
+        try await reopened.close()
+        _ = try await storeManager.remove(specUri: URI_SCHEMA + storeURL.path)
+    }
+
+    func testProfiles() async throws {
+        let defaultProfile = try await store.getDefaultProfile()
+        XCTAssertEqual(try await store.getProfileName(), defaultProfile)
+
+        session = try await store.session(profile: nil)
+        try await session.update(
+            operation: .insert,
+            category: TEST_ENTRY["category"]!,
+            name: TEST_ENTRY["name"]!,
+            value: Data(TEST_ENTRY["value"]!.utf8),
+            tags: TEST_TAGS,
+            expiryMs: nil)
+
+        let tenant = try await store.createProfile(profile: "tenant")
+        var profiles = try await store.listProfiles()
+        XCTAssertEqual(Set(profiles), Set([defaultProfile, tenant]))
+
+        try await store.setDefaultProfile(profile: tenant)
+        XCTAssertEqual(try await store.getDefaultProfile(), tenant)
+        try await store.setDefaultProfile(profile: defaultProfile)
+
+        let stats = try await store.copyProfile(fromProfile: defaultProfile, toProfile: "clone")
+        XCTAssertEqual(stats.entries, 1)
+        try await store.renameProfile(fromProfile: "clone", toProfile: "renamed")
+        profiles = try await store.listProfiles()
+        XCTAssertEqual(Set(profiles), Set([defaultProfile, tenant, "renamed"]))
+
+        let renamed = try await store.session(profile: "renamed")
+        let count = try await renamed.count(category: TEST_ENTRY["category"]!, tagFilter: nil)
+        XCTAssertEqual(count, 1)
+        try await renamed.close()
+    }
+
     func testScan() async throws {
         session = try await store.session(profile: nil)
         try await session.update(
@@ -118,10 +381,10 @@ final class StoreTests: XCTestCase {
             category: TEST_ENTRY["category"]!,
             name: TEST_ENTRY["name"]!,
             value: Data(TEST_ENTRY["value"]!.utf8),
-            tags: TEST_ENTRY["tags"]!,
+            tags: TEST_TAGS,
             expiryMs: nil)
 
-        let scan = try await store.scan(profile: nil, category: TEST_ENTRY["category"]!, tagFilter: "{\"~plaintag\": \"a\", \"enctag\": \"b\"}", offset: nil, limit: nil)
+        let scan = try await store.scan(profile: nil, category: TEST_ENTRY["category"]!, tagFilter: AskarTagFilter.fromJson(json: "{\"~plaintag\": \"a\", \"enctag\": \"b\"}"), offset: nil, limit: nil, pageSize: nil)
         let rows = try await scan.fetchAll()
         XCTAssertEqual(rows.count, 1)
         let first = rows[0]
@@ -129,17 +392,146 @@ final class StoreTests: XCTestCase {
         XCTAssertEqual(String(bytes: first.value(), encoding: .utf8), TEST_ENTRY["value"]!)
     }
 
+    func testExpiry() async throws {
+        session = try await store.session(profile: nil)
+        let before = Int64(Date().timeIntervalSince1970 * 1000)
+        try await session.update(operation: .insert, category: "queue", name: "soon", value: Data("a".utf8), tags: TEST_TAGS, expiryMs: 60_000)
+        try await session.update(operation: .insert, category: "queue", name: "later", value: Data("b".utf8), tags: nil, expiryMs: 3_600_000)
+        try await session.update(operation: .insert, category: "queue", name: "never", value: Data("c".utf8), tags: nil, expiryMs: nil)
+
+        let soon = try await session.fetch(category: "queue", name: "soon", forUpdate: false)!
+        XCTAssertGreaterThanOrEqual(soon.expiry()!, before + 60_000)
+        XCTAssertEqual(soon.tags(), ["plaintag": "a", "enctag": "b"])
+        XCTAssertNil(try await session.fetch(category: "queue", name: "never", forUpdate: false)!.expiry())
+
+        let expiring = try await session.fetchAll(category: "queue", tagFilter: AskarTagFilter.expiresWithin(durationMs: 120_000), limit: nil, forUpdate: false)
+        XCTAssertEqual(expiring.map { $0.name() }, ["soon"])
+        let remaining = try await session.fetchAll(category: "queue", tagFilter: AskarTagFilter.negate(filter: AskarTagFilter.expiresWithin(durationMs: 120_000)), limit: nil, forUpdate: false)
+        XCTAssertEqual(Set(remaining.map { $0.name() }), ["later", "never"])
+
+        let keypair = try keyFactory.generate(alg: .ed25519, ephemeral: false)
+        try await session.insertKey(name: "expiring_key", key: keypair, metadata: nil, reference: nil, tags: nil, expiryMs: 60_000)
+        let key = try await session.fetchKey(name: "expiring_key", forUpdate: false)
+        XCTAssertGreaterThanOrEqual(key!.expiry()!, before + 60_000)
+        XCTAssertEqual(key!.tags(), [:])
+        try await session.updateKey(name: "expiring_key", metadata: nil, tags: nil, expiryMs: nil)
+        XCTAssertNil(try await session.fetchKey(name: "expiring_key", forUpdate: false)!.expiry())
+        // As in aries-askar, tags which are not given are cleared, even when setting an expiry
+        try await session.updateKey(name: "expiring_key", metadata: nil, tags: TEST_TAGS, expiryMs: nil)
+        try await session.updateKey(name: "expiring_key", metadata: nil, tags: nil, expiryMs: 60_000)
+        let updated = try await session.fetchKey(name: "expiring_key", forUpdate: false)
+        XCTAssertEqual(updated!.tags(), [:])
+        XCTAssertGreaterThanOrEqual(updated!.expiry()!, before + 60_000)
+
+        try await session.update(operation: .insert, category: "queue", name: "expired", value: Data("d".utf8), tags: nil, expiryMs: 1)
+        try await Task.sleep(nanoseconds: 10_000_000)
+        XCTAssertEqual(try await store.purgeExpired(profile: nil), 1)
+    }
+
+    func testSubscription() async throws {
+        let listener = TestEntryListener()
+        let filter = AskarTagFilter.isEq(name: "~plaintag", value: "a")
+        let subscription = try await store.subscribe(profile: nil, category: "connection", tagFilter: filter, listener: listener)
+
+        session = try await store.session(profile: nil)
+        try await session.update(operation: .insert, category: "connection", name: "tagged", value: Data("a".utf8), tags: TEST_TAGS, expiryMs: nil)
+        try await session.update(operation: .insert, category: "connection", name: "untagged", value: Data("b".utf8), tags: nil, expiryMs: nil)
+        try await session.update(operation: .insert, category: "other", name: "tagged", value: Data("c".utf8), tags: TEST_TAGS, expiryMs: nil)
+        try await session.update(operation: .replace, category: "connection", name: "tagged", value: Data("d".utf8), tags: TEST_TAGS, expiryMs: nil)
+        XCTAssertEqual(listener.events.map { $0.operation }, [.insert, .replace])
+        XCTAssertEqual(listener.events.map { $0.name }, ["tagged", "tagged"])
+
+        let txn = try await store.transaction(profile: nil)
+        try await txn.update(operation: .remove, category: "connection", name: "tagged", value: Data(), tags: nil, expiryMs: nil)
+        XCTAssertEqual(listener.events.count, 2)
+        try await txn.commit()
+        XCTAssertEqual(listener.events.last?.operation, .remove)
+        XCTAssertEqual(listener.events.count, 3)
+
+        // The entries removed together are not known, so one event is reported for them
+        XCTAssertEqual(try await session.removeAll(category: "connection", tagFilter: nil), 1)
+        XCTAssertEqual(listener.events.count, 4)
+        XCTAssertEqual(listener.events.last?.operation, .remove)
+        XCTAssertNil(listener.events.last?.name)
+        XCTAssertEqual(try await session.removeAll(category: "connection", tagFilter: nil), 0)
+        XCTAssertEqual(listener.events.count, 4)
+
+        subscription.cancel()
+        try await session.update(operation: .insert, category: "connection", name: "tagged", value: Data("e".utf8), tags: TEST_TAGS, expiryMs: nil)
+        XCTAssertEqual(listener.events.count, 4)
+
+        for unsupported in [AskarTagFilter.isGt(name: "enctag", value: "a"), AskarTagFilter.isLike(name: "~plaintag", value: "a%")] {
+            do {
+                _ = try await store.subscribe(profile: nil, category: "connection", tagFilter: unsupported, listener: listener)
+                XCTFail("Subscription filters only support equality and set operators")
+            } catch {}
+        }
+    }
+
+    func testPostgresStore() async throws {
+        // Requires a wrapper built with the postgres feature
+        guard let postgresUri = ProcessInfo.processInfo.environment["POSTGRES_URL"] else {
+            throw XCTSkip("POSTGRES_URL is not set")
+        }
+        let key = try storeManager.generateRawStoreKey(seed: nil)
+        let postgresStore = try await storeManager.provision(specUri: postgresUri, keyMethod: .raw, passKey: key, profile: nil, recreate: true)
+        let postgresSession = try await postgresStore.session(profile: nil)
+        for i in 0..<5 {
+            try await postgresSession.update(operation: .insert, category: TEST_ENTRY["category"]!, name: "name\(i)", value: Data(TEST_ENTRY["value"]!.utf8), tags: TEST_TAGS, expiryMs: nil)
+        }
+        try await postgresSession.close()
+
+        let scan = try await postgresStore.scan(profile: nil, category: TEST_ENTRY["category"]!, tagFilter: AskarTagFilter.isEq(name: "enctag", value: "b"), offset: nil, limit: nil, pageSize: 2)
+        var names: [String] = []
+        while let batch = try await scan.next() {
+            names += batch.map { $0.name() }
+        }
+        XCTAssertEqual(Set(names), ["name0", "name1", "name2", "name3", "name4"])
+        try await postgresStore.close()
+
+        let reopened = try await storeManager.open(specUri: postgresUri, keyMethod: .raw, passKey: key, profile: nil)
+        let reopenedSession = try await reopened.session(profile: nil)
+        XCTAssertEqual(try await reopenedSession.count(category: TEST_ENTRY["category"]!, tagFilter: nil), 5)
+        try await reopenedSession.close()
+        try await reopened.close()
+        XCTAssertTrue(try await storeManager.remove(specUri: postgresUri))
+    }
+
+    func testScanPaging() async throws {
+        session = try await store.session(profile: nil)
+        for idx in 0..<10 {
+            try await session.update(
+                operation: .insert,
+                category: TEST_ENTRY["category"]!,
+                name: "name\(idx)",
+                value: Data(TEST_ENTRY["value"]!.utf8),
+                tags: nil,
+                expiryMs: nil)
+        }
+
+        let scan = try await store.scan(profile: nil, category: TEST_ENTRY["category"]!, tagFilter: nil, offset: 1, limit: 7, pageSize: 3)
+        var pages: [[String]] = []
+        while let batch = try await scan.next() {
+            pages.append(batch.map { $0.name() })
+        }
+        XCTAssertEqual(pages, [["name1", "name2", "name3"], ["name4", "name5", "name6"], ["name7"]])
+
+        let closed = try await store.scan(profile: nil, category: TEST_ENTRY["category"]!, tagFilter: nil, offset: nil, limit: nil, pageSize: 2)
+        XCTAssertEqual(try await closed.next()?.count, 2)
+        try await closed.close()
+    }
+
     func testKeyStore() async throws {
         session = try await store.session(profile: nil)
         let keypair = try keyFactory.generate(alg: .ed25519, ephemeral: false)
         let keyName = "test_key"
-        try await session.insertKey(name: keyName, key: keypair, metadata: "metadata", tags: "{\"a\": \"b\"}", expiryMs: nil)
+        try await session.insertKey(name: keyName, key: keypair, metadata: "metadata", reference: nil, tags: [AskarEntryTag(name: "a", value: "b", plaintext: false)], expiryMs: nil)
 
         var key = try await session.fetchKey(name: keyName, forUpdate: false)
         XCTAssertEqual(key?.name(), keyName)
         XCTAssertEqual(key?.tags()["a"], "b")
 
-        try await session.updateKey(name: keyName, metadata: "new metadata", tags: "{\"a\": \"c\"}", expiryMs: nil)
+        try await session.updateKey(name: keyName, metadata: "new metadata", tags: [AskarEntryTag(name: "a", value: "c", plaintext: false)], expiryMs: nil)
         key = try await session.fetchKey(name: keyName, forUpdate: false)
         XCTAssertEqual(key?.name(), keyName)
         XCTAssertEqual(key?.tags()["a"], "c")
@@ -147,7 +539,7 @@ final class StoreTests: XCTestCase {
         let thumbprint = try keypair.toJwkThumbprint(alg: nil)
         XCTAssertEqual(try key?.loadLocalKey().toJwkThumbprint(alg: nil), thumbprint)
 
-        let keylist = try await session.fetchAllKeys(algorithm: "ed25519", thumbprint: thumbprint, tagFilter: "{\"a\": \"c\"}", limit: -1, forUpdate: false)
+        let keylist = try await session.fetchAllKeys(algorithm: "ed25519", thumbprint: thumbprint, tagFilter: AskarTagFilter.isEq(name: "a", value: "c"), limit: -1, forUpdate: false)
         XCTAssertEqual(keylist.count, 1)
         XCTAssertEqual(keylist[0].name(), keyName)
 
@@ -156,6 +548,83 @@ final class StoreTests: XCTestCase {
         XCTAssertNil(key)
     }
 
+    func testRotateKey() async throws {
+        session = try await store.session(profile: nil)
+        let first = try keyFactory.generate(alg: .ed25519, ephemeral: false)
+        try await session.insertKey(name: "did_key", key: first, metadata: "meta", reference: nil, tags: [AskarEntryTag(name: "a", value: "b", plaintext: false)], expiryMs: nil)
+        do {
+            try await session.insertKey(name: "askar:history:did_key@v1", key: first, metadata: nil, reference: nil, tags: nil, expiryMs: nil)
+            XCTFail("Reserved key names should not be used")
+        } catch {}
+
+        let second = try keyFactory.generate(alg: .ed25519, ephemeral: false)
+        do {
+            _ = try await session.rotateKey(name: "did_key", newKey: second)
+            XCTFail("Keys should only be rotated within a transaction")
+        } catch {}
+        let txn = try await store.transaction(profile: nil)
+        XCTAssertEqual(try await txn.rotateKey(name: "did_key", newKey: second), 2)
+        let third = try keyFactory.generate(alg: .ed25519, ephemeral: false)
+        XCTAssertEqual(try await txn.rotateKey(name: "did_key", newKey: third), 3)
+        try await txn.commit()
+
+        let current = try await session.fetchKey(name: "did_key", forUpdate: false)
+        XCTAssertEqual(current?.version(), 3)
+        XCTAssertEqual(current?.metadata(), "meta")
+        XCTAssertEqual(current?.tags(), ["a": "b"])
+        XCTAssertEqual(try current?.loadLocalKey().toPublicBytes(), try third.toPublicBytes())
+
+        let previous = try await session.fetchKeyVersion(name: "did_key", version: 1)
+        XCTAssertEqual(previous?.version(), 1)
+        XCTAssertEqual(try previous?.loadLocalKey().toPublicBytes(), try first.toPublicBytes())
+        XCTAssertEqual(previous?.tags()["a"], "b")
+        XCTAssertNotNil(previous?.tags()["rotated_at"])
+        XCTAssertEqual(try await session.fetchKeyVersion(name: "did_key", version: 3)?.version(), 3)
+        XCTAssertNil(try await session.fetchKeyVersion(name: "did_key", version: 4))
+
+        // Removing a rotated key also removes its previous versions
+        do {
+            try await session.removeKey(name: "did_key")
+            XCTFail("Rotated keys should only be removed within a transaction")
+        } catch {}
+        let removeTxn = try await store.transaction(profile: nil)
+        try await removeTxn.removeKey(name: "did_key")
+        try await removeTxn.commit()
+        XCTAssertNil(try await session.fetchKeyVersion(name: "did_key", version: 1))
+        XCTAssertNil(try await session.fetchKeyVersion(name: "did_key", version: 2))
+
+        try await session.insertKey(name: "did_key", key: first, metadata: nil, reference: nil, tags: nil, expiryMs: nil)
+        let rotateTxn = try await store.transaction(profile: nil)
+        XCTAssertEqual(try await rotateTxn.rotateKey(name: "did_key", newKey: second), 2)
+        try await rotateTxn.commit()
+        XCTAssertEqual(try await session.fetchKeyVersion(name: "did_key", version: 1)?.loadLocalKey().toPublicBytes(), try first.toPublicBytes())
+    }
+
+    func testExternalKey() async throws {
+        session = try await store.session(profile: nil)
+        let handler = TestExternalKeyHandler(key: try keyFactory.generate(alg: .ed25519, ephemeral: false))
+        let publicKey = try keyFactory.fromPublicBytes(alg: .ed25519, bytes: try handler.key.toPublicBytes())
+        try await session.insertKey(name: "external", key: publicKey, metadata: nil, reference: .external(id: "keystore-1"), tags: [AskarEntryTag(name: "a", value: "b", plaintext: false)], expiryMs: nil)
+
+        var key = try await session.fetchKey(name: "external", forUpdate: false)
+        XCTAssertEqual(key?.reference(), .external(id: "keystore-1"))
+        XCTAssertFalse(key!.isLocal())
+        XCTAssertEqual(key?.tags(), ["a": "b"])
+
+        let message = Data("test message".utf8)
+        XCTAssertThrowsError(try key!.signMessage(message: message, sigType: nil, handler: nil))
+        let signature = try key!.signMessage(message: message, sigType: nil, handler: handler)
+        XCTAssertEqual(handler.ids, ["keystore-1"])
+        XCTAssertTrue(try publicKey.verifySignature(message: message, signature: signature, sigType: nil))
+
+        try await session.updateKey(name: "external", metadata: nil, tags: [AskarEntryTag(name: "a", value: "c", plaintext: false)], expiryMs: nil)
+        key = try await session.fetchKey(name: "external", forUpdate: false)
+        XCTAssertEqual(key?.reference(), .external(id: "keystore-1"))
+
+        let keylist = try await session.fetchAllKeys(algorithm: "ed25519", thumbprint: nil, tagFilter: nil, limit: -1, forUpdate: false)
+        XCTAssertEqual(keylist.count, 1)
+    }
+
     func testMigration() async throws {
         guard let file = Bundle.module.url(forResource: "indy_wallet_sqlite", withExtension: "db") else {
             XCTFail("Wallet file not found")
@@ -165,17 +634,84 @@ final class StoreTests: XCTestCase {
         let copyPath = "/tmp/indy_wallet_sqlite_upgrade.db"
         try FileManager.default.copyItem(atPath: file.path, toPath: copyPath)
         let key = "GfwU1DC7gEZNs3w41tjBiZYj7BNToDoFEqKY6wZXqs1A"
-        try await storeManager.migrateIndyWallet(specUri: URI_SCHEMA + copyPath,
-                                                 walletName: "walletwallet.0",
-                                                 walletKey: key,
-                                                 kdfLevel: "RAW")
+        do {
+            _ = try await storeManager.migrateIndyWallet(specUri: URI_SCHEMA + copyPath,
+                                                         walletName: "walletwallet.0",
+                                                         walletKey: "3vvPmfqTaKhP4bqyTwSMhjGdXW2VKFrZJiLjH8mRyDzB",
+                                                         kdfLevel: "RAW",
+                                                         dryRun: true,
+                                                         progress: nil)
+            XCTFail("Migrating with the wrong wallet key should fail")
+        } catch {}
+
+        let dryRun = try await storeManager.migrateIndyWallet(specUri: URI_SCHEMA + copyPath,
+                                                              walletName: "walletwallet.0",
+                                                              walletKey: key,
+                                                              kdfLevel: "RAW",
+                                                              dryRun: true,
+                                                              progress: nil)
+        XCTAssertTrue(dryRun.dryRun)
+        XCTAssertEqual(dryRun.migratedItems, dryRun.totalItems)
+
+        let progress = TestMigrationProgress()
+        let report = try await storeManager.migrateIndyWallet(specUri: URI_SCHEMA + copyPath,
+                                                              walletName: "walletwallet.0",
+                                                              walletKey: key,
+                                                              kdfLevel: "RAW",
+                                                              dryRun: false,
+                                                              progress: progress)
+        XCTAssertEqual(report.totalItems, dryRun.totalItems)
+        XCTAssertEqual(report.categories, dryRun.categories)
+        XCTAssertEqual(progress.updates.last?.0, .verifying)
+        XCTAssertEqual(progress.updates.last?.1, report.totalItems)
 
         // Try open the upgraded wallet
         let upgraded = try await storeManager.open(specUri: URI_SCHEMA + copyPath,
-                                                               keyMethod: "raw",
-                                                               passKey: key,
+                                                               keyMethod: .raw,
+                                                               passKey: .rawKey(key: key),
                                                                profile: nil)
         try await upgraded.close()
         _ = try await storeManager.remove(specUri: URI_SCHEMA + copyPath)
     }
 }
+
+class TestExternalKeyHandler: AskarExternalKeyHandler {
+    let key: AskarLocalKey
+    var ids: [String] = []
+
+    init(key: AskarLocalKey) {
+        self.key = key
+    }
+
+    func signMessage(id: String, message: Data, sigType: String?) throws -> Data {
+        ids.append(id)
+        return try key.signMessage(message: message, sigType: sigType)
+    }
+
+    func verifySignature(id: String, message: Data, signature: Data, sigType: String?) throws -> Bool {
+        ids.append(id)
+        return try key.verifySignature(message: message, signature: signature, sigType: sigType)
+    }
+
+    func keyExchange(id: String, alg: AskarKeyAlg, publicJwk: String) throws -> Data {
+        ids.append(id)
+        let pk = try LocalKeyFactory().fromJwk(jwk: publicJwk)
+        return try key.toKeyExchange(alg: alg, pk: pk).toSecretBytes()
+    }
+}
+
+class TestEntryListener: AskarEntryListener {
+    var events: [AskarEntryEvent] = []
+
+    func onEvent(event: AskarEntryEvent) {
+        events.append(event)
+    }
+}
+
+class TestMigrationProgress: AskarMigrationProgress {
+    var updates: [(AskarMigrationStage, Int64, Int64)] = []
+
+    func onProgress(stage: AskarMigrationStage, completed: Int64, total: Int64) {
+        updates.append((stage, completed, total))
+    }
+}